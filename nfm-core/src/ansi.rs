@@ -0,0 +1,636 @@
+// nfm-core::ansi: Renders No-Flavor Markdown for a terminal.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown for a terminal, styled with ansi escape
+//! sequences and wrapped to the width of the terminal. Links are written as
+//! OSC 8 hyperlinks, which terminals without support show as plain text.
+
+use crate::{
+    text::ordered_markers,
+    tree::{ self, Element, Node, },
+};
+
+/// The colour of headings.
+const HEADING: u8 = 5;
+/// The colour of links and footnote references.
+const LINK: u8 = 4;
+
+/// Renders the html written by the parser for a terminal of a width.
+pub fn render(html: &str, width: usize) -> String {
+    let mut output = blocks(&tree::parse(html), width.max(1)).join("\n\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+/// The style text is written in.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Style {
+    bold: bool,
+    dim: bool,
+    italic: bool,
+    underline: bool,
+    reverse: bool,
+    strike: bool,
+    /// Whether the text is code, which is set on a dark background.
+    code: bool,
+    color: Option<u8>,
+    /// The url the text links to.
+    link: Option<String>,
+}
+
+impl Style {
+    /// Writes text in the style, resetting the style after it.
+    fn paint(&self, text: &str) -> String {
+        let mut codes = Vec::new();
+        for (set, code) in [
+            (self.bold, "1"), (self.dim, "2"), (self.italic, "3"), (self.underline, "4"),
+            (self.reverse, "7"), (self.strike, "9"), (self.code, "48;5;236"),
+        ] {
+            if set {
+                codes.push(code.to_owned());
+            }
+        }
+
+        if let Some(color) = self.color {
+            codes.push(format!("3{color}"));
+        }
+
+        let mut painted = String::new();
+        if let Some(url) = &self.link {
+            painted.push_str(&format!("\x1b]8;;{url}\x1b\\"));
+        }
+
+        if codes.is_empty() {
+            painted.push_str(text);
+        } else {
+            painted.push_str(&format!("\x1b[{}m{text}\x1b[0m", codes.join(";")));
+        }
+
+        if self.link.is_some() {
+            painted.push_str("\x1b]8;;\x1b\\");
+        }
+
+        painted
+    }
+}
+
+/// The number of columns text takes up, leaving out escape sequences.
+fn visible_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += 1;
+            continue;
+        }
+
+        match chars.next() {
+            // styles end with a letter
+            Some('[') => for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            },
+            // hyperlinks end with the string terminator
+            Some(']') => while let Some(c) = chars.next() {
+                if c == '\x1b' {
+                    chars.next();
+                    break;
+                }
+            },
+            _ => {},
+        }
+    }
+
+    width
+}
+
+/// A piece of inline content.
+enum Run {
+    Text(String, Style),
+    Break,
+}
+
+/// Reads inline nodes into runs of styled text.
+fn runs(nodes: &[Node], style: &Style, runs: &mut Vec<Run>) {
+    for node in nodes {
+        let element = match node {
+            Node::Text(text) => {
+                runs.push(Run::Text(text.clone(), style.clone()));
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        let mut inner = style.clone();
+        match element.name.as_str() {
+            "br" => {
+                runs.push(Run::Break);
+                continue;
+            },
+            "strong" => inner.bold = true,
+            "em" => inner.italic = true,
+            "ins" => inner.underline = true,
+            "del" => inner.strike = true,
+            "mark" => inner.reverse = true,
+            "code" => inner.code = true,
+            "input" => {
+                let checkbox = if element.has_attribute("checked") { "☑" } else { "☐" };
+                runs.push(Run::Text(checkbox.to_owned(), style.clone()));
+                continue;
+            },
+            "img" => {
+                let src = element.attribute("src").unwrap_or_default();
+                let alt = element.attribute("alt").filter(|a| !a.is_empty()).unwrap_or(src);
+                inner.italic = true;
+                inner.link = Some(src.to_owned());
+                runs.push(Run::Text(alt.to_owned(), inner));
+                continue;
+            },
+            "sup" => if let Some(number) = element.footnote_reference() {
+                inner.color = Some(LINK);
+                runs.push(Run::Text(format!("[{number}]"), inner));
+                continue;
+            },
+            "a" => match element.attribute("href") {
+                // links back to a footnote reference are only useful in html
+                Some(href) if href.starts_with("#fnref-") => continue,
+                Some(href) if href.starts_with('#') => {},
+                Some(href) => {
+                    inner.underline = true;
+                    inner.color = Some(LINK);
+                    inner.link = Some(href.to_owned());
+                    if element.text().is_empty() {
+                        runs.push(Run::Text(href.to_owned(), inner));
+                        continue;
+                    }
+                },
+                None => {},
+            },
+            _ => {},
+        }
+
+        self::runs(&element.children, &inner, runs);
+    }
+}
+
+/// Lines of styled text being filled with words.
+struct Lines {
+    lines: Vec<String>,
+    line: String,
+    /// The columns taken up by the line.
+    used: usize,
+    width: usize,
+}
+
+impl Lines {
+    /// Adds a word made up of pieces of styled text, preceded by a space in a
+    /// style when it doesn't begin a line.
+    fn word(&mut self, word: &mut Vec<(String, &Style)>, space: Option<&Style>) {
+        if word.is_empty() {
+            return;
+        }
+
+        let len = word.iter().map(|(t, _)| t.chars().count()).sum::<usize>();
+        if self.used > 0 && self.used + 1 + len > self.width {
+            self.end_line();
+        } else if let (true, Some(style)) = (self.used > 0, space) {
+            self.line.push_str(&style.paint(" "));
+            self.used += 1;
+        }
+
+        for (text, style) in word.drain(..) {
+            self.line.push_str(&style.paint(&text));
+        }
+        self.used += len;
+    }
+
+    fn end_line(&mut self) {
+        self.lines.push(std::mem::take(&mut self.line));
+        self.used = 0;
+    }
+}
+
+/// Wraps runs of text at a width, collapsing whitespace as html would. Words
+/// longer than the width are left whole.
+fn wrap(runs: &[Run], width: usize) -> Vec<String> {
+    let mut lines = Lines { lines: Vec::new(), line: String::new(), used: 0, width, };
+    // the pieces of the word being read, with the style of each
+    let mut word: Vec<(String, &Style)> = Vec::new();
+    // the style of the space before the word
+    let mut space: Option<&Style> = None;
+
+    for run in runs {
+        let (text, style) = match run {
+            Run::Text(text, style) => (text, style),
+            Run::Break => {
+                lines.word(&mut word, space);
+                lines.end_line();
+                space = None;
+                continue;
+            },
+        };
+
+        for c in text.chars() {
+            if c.is_whitespace() {
+                lines.word(&mut word, space);
+                space = Some(style);
+                continue;
+            }
+
+            match word.last_mut() {
+                Some((text, s)) if *s == style => text.push(c),
+                _ => word.push((c.to_string(), style)),
+            }
+        }
+    }
+
+    lines.word(&mut word, space);
+    if lines.used > 0 {
+        lines.end_line();
+    }
+
+    lines.lines
+}
+
+fn inline(nodes: &[Node], style: &Style, width: usize) -> String {
+    let mut read = Vec::new();
+    runs(nodes, style, &mut read);
+    wrap(&read, width).join("\n")
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into blocks of their own.
+fn blocks(nodes: &[Node], width: usize) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let text = inline(&nodes[start..$end], &Style::default(), width);
+            if !text.trim().is_empty() {
+                blocks.push(text);
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+                if let Some(block) = block(element, width) {
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+    blocks
+}
+
+fn block(element: &Element, width: usize) -> Option<String> {
+    let text = match element.name.as_str() {
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6" => {
+            let style = match element.name.as_str() {
+                "h1" => Style { bold: true, underline: true, color: Some(HEADING), ..Default::default() },
+                "h2" => Style { bold: true, color: Some(HEADING), ..Default::default() },
+                _ => Style { bold: true, ..Default::default() },
+            };
+            inline(&element.children, &style, width)
+        },
+        "p" => inline(&element.children, &Style::default(), width),
+        "caption" => {
+            let style = Style { italic: true, ..Default::default() };
+            inline(&element.children, &style, width)
+        },
+        "hr" => Style { dim: true, ..Default::default() }.paint(&"─".repeat(width)),
+        "pre" => code_block(element),
+        "blockquote" => {
+            let bar = Style { dim: true, ..Default::default() }.paint("│");
+            blocks(&element.children, width.saturating_sub(2).max(1)).join("\n\n")
+                .split('\n')
+                .map(|l| if l.is_empty() { bar.clone() } else { format!("{bar} {l}") })
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        "ul"|"ol" => list(element, width),
+        "table" => table(element),
+        "section" if element.attribute("class") == Some("footnotes") => {
+            footnotes(element, width)
+        },
+        "colgroup" => return None,
+        _ => blocks(&element.children, width).join("\n\n"),
+    };
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Writes code on a dark background, padded to the width of its longest
+/// line. Code is never wrapped.
+fn code_block(element: &Element) -> String {
+    let text = element.text();
+    let lines = text.trim_end_matches('\n').split('\n').collect::<Vec<_>>();
+    let longest = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let style = Style { code: true, ..Default::default() };
+
+    lines.iter()
+        .map(|l| style.paint(&format!(" {l:<longest$} ")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Prefixes the first line of text with a marker and indents the rest to
+/// line up with it.
+fn hang(marker: &str, text: &str) -> String {
+    let indent = " ".repeat(visible_width(marker) + 1);
+    text.split('\n')
+        .enumerate()
+        .map(|(i, l)| match (i, l.is_empty()) {
+            (0, _) => format!("{marker} {l}").trim_end().to_owned(),
+            (_, true) => String::new(),
+            (_, false) => format!("{indent}{l}"),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn list(element: &Element, width: usize) -> String {
+    let items = element.elements()
+        .filter(|e| e.name == "li")
+        .collect::<Vec<_>>();
+
+    let markers = if element.name == "ol" {
+        ordered_markers(element, items.len())
+    } else {
+        vec!["•".to_owned(); items.len()]
+    };
+
+    items.iter()
+        .zip(markers)
+        .map(|(item, marker)| {
+            let available = width.saturating_sub(marker.chars().count() + 1).max(1);
+            hang(&marker, &blocks(&item.children, available).join("\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn footnotes(element: &Element, width: usize) -> String {
+    let mut lines = vec![ Style { dim: true, ..Default::default() }.paint(&"─".repeat(width.min(20))), ];
+    for list in element.elements() {
+        for item in list.elements() {
+            let number = item.attribute("id")
+                .and_then(|id| id.strip_prefix("fn-"))
+                .unwrap_or_default();
+            let marker = Style { color: Some(LINK), ..Default::default() }.paint(&format!("[{number}]"));
+            let available = width.saturating_sub(number.chars().count() + 3).max(1);
+            lines.push(hang(&marker, &blocks(&item.children, available).join("\n")));
+        }
+    }
+
+    lines.join("\n")
+}
+
+struct Cell {
+    lines: Vec<String>,
+    row: usize,
+    column: usize,
+    rowspan: usize,
+    colspan: usize,
+    align: String,
+}
+
+/// Draws a table with box characters, joining the borders of cells which
+/// span several columns or rows.
+fn table(element: &Element) -> String {
+    let mut caption = None;
+    let mut rows = Vec::new();
+
+    for child in element.elements() {
+        match child.name.as_str() {
+            "caption" => caption = block(child, usize::MAX),
+            "thead"|"tbody"|"tfoot" => rows.extend(child.elements().filter(|e| e.name == "tr")),
+            "tr" => rows.push(child),
+            _ => {},
+        }
+    }
+
+    // the cell covering each column of each row
+    let mut grid: Vec<Vec<Option<usize>>> = vec![Vec::new(); rows.len()];
+    let mut cells = Vec::new();
+
+    for (r, row) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in row.elements().filter(|e| e.name == "td" || e.name == "th") {
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+
+            let colspan = cell.span("colspan");
+            let rowspan = cell.span("rowspan").min(rows.len() - r);
+            for covered in &mut grid[r..r + rowspan] {
+                if covered.len() < column + colspan {
+                    covered.resize(column + colspan, None);
+                }
+                for c in &mut covered[column..column + colspan] {
+                    *c = Some(cells.len());
+                }
+            }
+
+            let style = Style { bold: cell.name == "th", ..Default::default() };
+            let mut read = Vec::new();
+            runs(&cell.children, &style, &mut read);
+            let lines = if cell.children.iter().any(Node::is_block) {
+                blocks(&cell.children, usize::MAX).join("\n").split('\n').map(str::to_owned).collect()
+            } else {
+                wrap(&read, usize::MAX)
+            };
+
+            cells.push(Cell {
+                lines,
+                row: r,
+                column,
+                rowspan,
+                colspan,
+                align: cell.alignment().unwrap_or("center").to_owned(),
+            });
+            column += colspan;
+        }
+    }
+
+    // columns no cell covers are given empty cells of their own
+    let columns = grid.iter().map(Vec::len).max().unwrap_or(0);
+    for (r, row) in grid.iter_mut().enumerate() {
+        row.resize(columns, None);
+        for (column, c) in row.iter_mut().enumerate() {
+            if c.is_none() {
+                *c = Some(cells.len());
+                cells.push(Cell {
+                    lines: Vec::new(),
+                    row: r,
+                    column,
+                    rowspan: 1,
+                    colspan: 1,
+                    align: "left".to_owned(),
+                });
+            }
+        }
+    }
+
+    let mut widths = vec![0; columns];
+    let mut heights = vec![1; rows.len()];
+    let cell_width = |cell: &Cell| cell.lines.iter().map(|l| visible_width(l)).max().unwrap_or(0);
+
+    for cell in cells.iter().filter(|c| c.colspan == 1) {
+        widths[cell.column] = widths[cell.column].max(cell_width(cell));
+    }
+    for cell in cells.iter().filter(|c| c.rowspan == 1) {
+        heights[cell.row] = heights[cell.row].max(cell.lines.len());
+    }
+
+    // spanning cells stretch the last of their columns and rows, and take in
+    // the borders between them
+    for cell in cells.iter().filter(|c| c.colspan > 1) {
+        let span = &widths[cell.column..cell.column + cell.colspan];
+        let available = span.iter().sum::<usize>() + 3 * (cell.colspan - 1);
+        if cell_width(cell) > available {
+            widths[cell.column + cell.colspan - 1] += cell_width(cell) - available;
+        }
+    }
+    for cell in cells.iter().filter(|c| c.rowspan > 1) {
+        let span = &heights[cell.row..cell.row + cell.rowspan];
+        let available = span.iter().sum::<usize>() + cell.rowspan - 1;
+        if cell.lines.len() > available {
+            heights[cell.row + cell.rowspan - 1] += cell.lines.len() - available;
+        }
+    }
+
+    let owner = |r: usize, c: usize| grid.get(r).and_then(|row| row.get(c)).copied().flatten();
+    // the owner of a column of a row, where the row or column may be outside
+    // of the table
+    let at = |r: isize, c: isize| if r < 0 || c < 0 {
+        None
+    } else {
+        owner(r as usize, c as usize)
+    };
+
+    // writes the line of a cell, padded to the width of its columns
+    let write_cell = |line: &mut String, cell: &Cell, index: usize| {
+        let span = widths[cell.column..cell.column + cell.colspan].iter().sum::<usize>() +
+            3 * (cell.colspan - 1);
+        let text = cell.lines.get(index).map(String::as_str).unwrap_or_default();
+        let padding = span.saturating_sub(visible_width(text));
+        let (left, right) = match cell.align.as_str() {
+            "right" => (padding, 0),
+            "center" => (padding / 2, padding - padding / 2),
+            _ => (0, padding),
+        };
+
+        line.push(' ');
+        line.push_str(&" ".repeat(left));
+        line.push_str(text);
+        line.push_str(&" ".repeat(right + 1));
+    };
+
+    // the line of a cell shown at a line of a row, counting the borders
+    // between the rows it spans
+    let index = |cell: &Cell, r: usize| heights[cell.row..r].iter().sum::<usize>() + r - cell.row;
+
+    let mut lines = Vec::new();
+    if let Some(caption) = caption {
+        lines.push(caption);
+    }
+
+    for b in 0..=rows.len() {
+        // the border above the row, through which spanning cells continue
+        let mut line = String::new();
+        let mut c = 0;
+        let b = b as isize;
+        while c <= columns {
+            let x = c as isize;
+            let up = at(b - 1, x - 1) != at(b - 1, x);
+            let down = at(b, x - 1) != at(b, x);
+            let left = at(b - 1, x - 1) != at(b, x - 1);
+            let right = at(b - 1, x) != at(b, x);
+            line.push(junction(up, down, left, right));
+
+            if c == columns {
+                break;
+            }
+
+            if right {
+                line.push_str(&"─".repeat(widths[c] + 2));
+                c += 1;
+            } else {
+                let cell = &cells[at(b, x).unwrap()];
+                write_cell(&mut line, cell, index(cell, b as usize) - 1);
+                c += cell.colspan;
+            }
+        }
+        lines.push(line.trim_end().to_owned());
+
+        if b as usize == rows.len() {
+            break;
+        }
+
+        let r = b as usize;
+        for l in 0..heights[r] {
+            let mut line = String::new();
+            let mut c = 0;
+            while c < columns {
+                let cell = &cells[owner(r, c).unwrap()];
+                line.push('│');
+                write_cell(&mut line, cell, index(cell, r) + l);
+                c += cell.colspan;
+            }
+            line.push('│');
+            lines.push(line);
+        }
+    }
+
+    lines.join("\n")
+}
+
+/// The box character joining borders in each direction.
+fn junction(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (false, false, false, false) => ' ',
+        (true, false, false, false) => '╵',
+        (false, true, false, false) => '╷',
+        (false, false, true, false) => '╴',
+        (false, false, false, true) => '╶',
+        (true, true, false, false) => '│',
+        (false, false, true, true) => '─',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
+}
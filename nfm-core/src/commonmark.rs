@@ -0,0 +1,357 @@
+// nfm-core::commonmark: Renders No-Flavor Markdown as CommonMark.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown as CommonMark, using the GitHub extensions for
+//! tables, deleted text, task lists and footnotes. Anything else is written as
+//! inline html.
+
+use crate::tree::{ self, Element, Node, };
+
+/// Renders the html written by the parser as CommonMark.
+pub fn render(html: &str) -> String {
+    let mut markdown = blocks(&tree::parse(html)).join("\n\n");
+    if !markdown.is_empty() {
+        markdown.push('\n');
+    }
+    markdown
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into blocks of their own.
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    // the kind of the last list written, and where it was written
+    let mut list: Option<(usize, &str)> = None;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let markdown = inline(&nodes[start..$end]);
+            let markdown = markdown.trim();
+            if !markdown.is_empty() {
+                blocks.push(markdown.to_owned());
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        let Node::Element(element) = node else {
+            continue;
+        };
+
+        if !element.is_block() {
+            continue;
+        }
+
+        push_inline!(i);
+        start = i + 1;
+
+        let Some(block) = block(element) else {
+            continue;
+        };
+
+        // lists written as html don't continue those before them
+        if (element.name == "ul" || element.name == "ol") && !block.starts_with('<') {
+            // adjacent lists of the same kind would otherwise become one
+            if list.is_some_and(|(idx, name)| idx + 1 == blocks.len() && name == element.name) {
+                blocks.push("<!-- -->".to_owned());
+            }
+
+            list = Some((blocks.len(), &element.name));
+        }
+
+        blocks.push(block);
+    }
+
+    push_inline!(nodes.len());
+    blocks
+}
+
+/// Prefixes every line but the first with an indent, leaving empty lines
+/// empty.
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    let mut lines = Vec::new();
+    for (i, line) in text.split('\n').enumerate() {
+        if i == 0 {
+            lines.push(format!("{first}{line}"));
+        } else if line.is_empty() {
+            lines.push(String::new());
+        } else {
+            lines.push(format!("{rest}{line}"));
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn block(element: &Element) -> Option<String> {
+    let markdown = match element.name.as_str() {
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6" => {
+            let level = element.name[1..].parse().unwrap_or(1);
+            let content = inline(&element.children);
+            format!("{} {}", "#".repeat(level), content.trim().replace('\n', " "))
+        },
+        "p" => inline(&element.children).trim().to_owned(),
+        "hr" => "---".to_owned(),
+        "pre" => code_block(element),
+        "blockquote" => blocks(&element.children).join("\n\n")
+            .lines()
+            .map(|l| if l.is_empty() { ">".to_owned() } else { format!("> {l}") })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "ul"|"ol" => list(element),
+        "table" => table(element),
+        "section" if element.attribute("class") == Some("footnotes") => {
+            footnotes(element)
+        },
+        _ => blocks(&element.children).join("\n\n"),
+    };
+
+    if markdown.is_empty() {
+        None
+    } else {
+        Some(markdown)
+    }
+}
+
+/// The length of the longest run of a character within text.
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for ch in text.chars() {
+        if ch == c {
+            run += 1;
+            longest = longest.max(run);
+        } else {
+            run = 0;
+        }
+    }
+    longest
+}
+
+fn code_block(element: &Element) -> String {
+    let code = element.elements().find(|e| e.name == "code");
+    let lang = code.and_then(|c| c.attribute("lang")).unwrap_or_default();
+    let text = element.text();
+    let fence = "`".repeat(3.max(longest_run(&text, '`') + 1));
+    format!("{fence}{lang}\n{}\n{fence}", text.trim_end_matches('\n'))
+}
+
+fn code_span(text: &str) -> String {
+    let fence = "`".repeat(longest_run(text, '`') + 1);
+    if text.starts_with('`') || text.ends_with('`') {
+        format!("{fence} {text} {fence}")
+    } else {
+        format!("{fence}{text}{fence}")
+    }
+}
+
+/// Escapes the characters of text which would otherwise be read as markup.
+fn push_text(markdown: &mut String, text: &str) {
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let line_start = markdown.is_empty() || markdown.ends_with('\n');
+        if line_start && matches!(c, '#'|'>'|'-'|'+'|'=') {
+            markdown.push('\\');
+        }
+
+        // a number beginning a line would begin an ordered list
+        if line_start && c.is_ascii_digit() {
+            markdown.push(c);
+            while let Some(d) = chars.next_if(char::is_ascii_digit) {
+                markdown.push(d);
+            }
+            if let Some(p) = chars.next_if(|p| *p == '.' || *p == ')') {
+                markdown.push('\\');
+                markdown.push(p);
+            }
+            continue;
+        }
+
+        match c {
+            '\\'|'`'|'*'|'_'|'['|']'|'<'|'~' => markdown.push('\\'),
+            '&' if chars.peek().is_some_and(|n| n.is_ascii_alphanumeric() || *n == '#') => {
+                markdown.push('\\');
+            },
+            _ => {},
+        }
+
+        markdown.push(c);
+    }
+}
+
+fn inline(nodes: &[Node]) -> String {
+    let mut markdown = String::new();
+
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::Text(t) => {
+                push_text(&mut markdown, t);
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        let content = || inline(&element.children);
+        match element.name.as_str() {
+            // the break is usually followed by the line break of the source
+            "br" => match nodes.get(i + 1) {
+                Some(Node::Text(t)) if t.starts_with('\n') => markdown.push('\\'),
+                _ => markdown.push_str("\\\n"),
+            },
+            "strong" => markdown.push_str(&format!("**{}**", content())),
+            "em" => markdown.push_str(&format!("_{}_", content())),
+            "del" => markdown.push_str(&format!("~~{}~~", content())),
+            "code" => markdown.push_str(&code_span(&element.text())),
+            "img" => markdown.push_str(&format!(
+                "![{}]({})",
+                inline(&[Node::Text(element.attribute("alt").unwrap_or_default().to_owned())]),
+                destination(element.attribute("src").unwrap_or_default()),
+            )),
+            "input" => if element.has_attribute("checked") {
+                markdown.push_str("[x]");
+            } else {
+                markdown.push_str("[ ]");
+            },
+            "sup" => match element.footnote_reference() {
+                Some(number) => markdown.push_str(&format!("[^{number}]")),
+                None => markdown.push_str(&format!("<sup>{}</sup>", content())),
+            },
+            "a" => match element.attribute("href") {
+                // links back to a footnote reference are written by the reader
+                Some(href) if href.starts_with("#fnref-") => {},
+                Some(href) => markdown.push_str(&format!("[{}]({})", content(), destination(href))),
+                None => markdown.push_str(&element.to_html()),
+            },
+            _ if element.is_block() => markdown.push_str(&element.to_html()),
+            name => markdown.push_str(&format!("<{name}>{}</{name}>", content())),
+        }
+    }
+
+    markdown
+}
+
+/// Writes the destination of a link, wrapping it in angle brackets when it
+/// contains spaces.
+fn destination(url: &str) -> String {
+    if url.contains(' ') {
+        format!("<{}>", url.replace('<', "\\<").replace('>', "\\>"))
+    } else {
+        url.replace('(', "\\(").replace(')', "\\)")
+    }
+}
+
+fn list(element: &Element) -> String {
+    // lettered, roman and reversed lists can't be written in markdown
+    let numbering = element.attribute("type").unwrap_or("1");
+    if element.name == "ol" && (numbering != "1" || element.has_attribute("reversed")) {
+        return element.to_html();
+    }
+
+    let start = element.attribute("start")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(1);
+
+    let mut items = Vec::new();
+    for (i, item) in element.elements().filter(|e| e.name == "li").enumerate() {
+        let marker = if element.name == "ol" {
+            format!("{}.", start + i)
+        } else {
+            "-".to_owned()
+        };
+
+        let rest = " ".repeat(marker.len() + 1);
+        let content = blocks(&item.children).join("\n");
+        items.push(indent(&content, &format!("{marker} "), &rest).trim_end().to_owned());
+    }
+
+    items.join("\n")
+}
+
+fn footnotes(element: &Element) -> String {
+    let mut definitions = Vec::new();
+    for list in element.elements() {
+        for item in list.elements() {
+            let number = item.attribute("id")
+                .and_then(|id| id.strip_prefix("fn-"))
+                .unwrap_or_default();
+            let content = blocks(&item.children).join("\n\n");
+            definitions.push(indent(content.trim(), &format!("[^{number}]: "), "    "));
+        }
+    }
+
+    definitions.join("\n")
+}
+
+/// Writes a table using the GitHub extension when it is a plain grid with a
+/// single row of column headers, and as html otherwise.
+fn table(element: &Element) -> String {
+    let mut head = Vec::new();
+    let mut body = Vec::new();
+
+    for child in element.elements() {
+        match child.name.as_str() {
+            "thead" => head.extend(child.elements().filter(|e| e.name == "tr")),
+            "tbody" => body.extend(child.elements().filter(|e| e.name == "tr")),
+            "tr" => body.push(child),
+            "colgroup" => {},
+            _ => return element.to_html(),
+        }
+    }
+
+    let cells = |row: &&Element| row.elements()
+        .filter(|e| e.name == "td" || e.name == "th")
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if head.len() != 1 {
+        return element.to_html();
+    }
+
+    let header = cells(&head[0]);
+    let rows = body.iter().map(cells).collect::<Vec<_>>();
+    let plain = header.iter().chain(rows.iter().flatten()).all(|c| {
+        c.span("colspan") == 1 && c.span("rowspan") == 1 &&
+            !c.children.iter().any(Node::is_block)
+    });
+
+    if !plain || rows.iter().any(|r| r.len() != header.len()) {
+        return element.to_html();
+    }
+
+    let row = |cells: &[Element]| {
+        let cells = cells.iter()
+            .map(|c| {
+                let content = inline(&c.children);
+                content.trim().replace('|', "\\|").replace("\\\n", "<br>").replace('\n', " ")
+            })
+            .collect::<Vec<_>>();
+        format!("| {} |", cells.join(" | "))
+    };
+
+    let delimiters = header.iter()
+        .map(|c| match c.alignment() {
+            Some("left") => ":--",
+            Some("right") => "--:",
+            Some("center") => ":-:",
+            _ => "---",
+        })
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![ row(&header), format!("| {} |", delimiters.join(" | ")), ];
+    lines.extend(rows.iter().map(|r| row(r)));
+    lines.join("\n")
+}
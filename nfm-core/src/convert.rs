@@ -0,0 +1,1364 @@
+// nfm-core::convert: Converts other flavors of markdown into No-Flavor Markdown.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Converts other flavors of markdown into No-Flavor Markdown.
+
+use {
+    crate::Diagnostic,
+    std::collections::{ HashMap, HashSet, },
+};
+
+/// The output of a converted document, along with the constructs which could
+/// not be converted.
+#[derive(Debug, Clone, Default)]
+pub struct Conversion {
+    pub output: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Converts CommonMark, along with the GitHub extensions for tables, deleted
+/// text, task lists and footnotes, into No-Flavor Markdown.
+pub fn from_commonmark(input: &str) -> Conversion {
+    let mut converter = Converter::new(input);
+    converter.convert();
+
+    let mut output = converter.output.join("\n");
+    let trimmed = output.trim_end_matches('\n').len();
+    output.truncate(trimmed);
+    if !output.is_empty() {
+        output.push('\n');
+    }
+
+    converter.diagnostics.sort_by_key(|d| d.line);
+    Conversion {
+        output,
+        diagnostics: converter.diagnostics,
+    }
+}
+
+/// Elements which begin an html block rather than inline html.
+const HTML_BLOCKS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "body", "caption", "center",
+    "dd", "details", "dialog", "div", "dl", "dt", "fieldset", "figcaption",
+    "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+    "header", "hr", "html", "iframe", "li", "main", "nav", "ol", "p", "pre",
+    "script", "section", "style", "summary", "table", "tbody", "td", "tfoot",
+    "th", "thead", "tr", "ul",
+];
+
+fn indentation(line: &str) -> usize {
+    let mut width = 0;
+    for c in line.chars() {
+        match c {
+            ' ' => width += 1,
+            '\t' => width += 4 - width % 4,
+            _ => break,
+        }
+    }
+    width
+}
+
+/// Removes up to `width` columns of indentation from a line.
+fn unindent(line: &str, width: usize) -> &str {
+    let mut removed = 0;
+    for (i, c) in line.char_indices() {
+        if removed >= width {
+            return &line[i..];
+        }
+
+        match c {
+            ' ' => removed += 1,
+            '\t' => removed += 4 - removed % 4,
+            _ => return &line[i..],
+        }
+    }
+
+    ""
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let line = line.trim();
+    let level = line.len() - line.trim_start_matches('#').len();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with([ ' ', '\t', ]) {
+        return None;
+    }
+
+    // an optional closing sequence of hashes
+    let rest = rest.trim();
+    let without = rest.trim_end_matches('#');
+    let text = if without.is_empty() || without.ends_with([ ' ', '\t', ]) {
+        without.trim_end()
+    } else {
+        rest
+    };
+
+    Some((level, text))
+}
+
+fn thematic_break(line: &str) -> bool {
+    if indentation(line) > 3 {
+        return false;
+    }
+
+    let line = line.trim();
+    let Some(c) = line.chars().next() else {
+        return false;
+    };
+
+    matches!(c, '-'|'*'|'_') &&
+        line.chars().all(|x| x == c || x == ' ' || x == '\t') &&
+        line.chars().filter(|x| *x == c).count() >= 3
+}
+
+fn setext_underline(line: &str) -> Option<usize> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let line = line.trim();
+    if !line.is_empty() && line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if !line.is_empty() && line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+/// The character, length, indentation and info string of an opening code
+/// fence.
+fn fence_open(line: &str) -> Option<(char, usize, usize, &str)> {
+    let indent = indentation(line);
+    if indent > 3 {
+        return None;
+    }
+
+    let trimmed = line.trim_start();
+    let c = trimmed.chars().next()?;
+    if c != '`' && c != '~' {
+        return None;
+    }
+
+    let len = trimmed.len() - trimmed.trim_start_matches(c).len();
+    let info = trimmed[len..].trim();
+    if len < 3 || (c == '`' && info.contains('`')) {
+        return None;
+    }
+
+    Some((c, len, indent, info))
+}
+
+fn fence_close(line: &str, c: char, len: usize) -> bool {
+    if indentation(line) > 3 {
+        return false;
+    }
+
+    let trimmed = line.trim();
+    let run = trimmed.len() - trimmed.trim_start_matches(c).len();
+    run >= len && run == trimmed.len()
+}
+
+fn blockquote_line(line: &str) -> Option<&str> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let rest = line.trim_start().strip_prefix('>')?;
+    Some(rest.strip_prefix(' ').unwrap_or(rest))
+}
+
+fn html_block_start(line: &str) -> bool {
+    if indentation(line) > 3 {
+        return false;
+    }
+
+    let line = line.trim();
+    if line.starts_with("<!--") || line.starts_with("<!") || line.starts_with("<?") {
+        return true;
+    }
+
+    let Some(rest) = line.strip_prefix('<') else {
+        return false;
+    };
+
+    let rest = rest.strip_prefix('/').unwrap_or(rest);
+    let name = rest.split(|c: char| !c.is_ascii_alphanumeric()).next().unwrap_or_default();
+    HTML_BLOCKS.contains(&name.to_lowercase().as_str())
+}
+
+struct ListItem<'a> {
+    indent: usize,
+    /// The character of the marker, or the delimiter following the number.
+    kind: char,
+    /// The number of the item, if it is ordered.
+    number: Option<usize>,
+    content_indent: usize,
+    content: &'a str,
+}
+
+fn list_item(line: &str) -> Option<ListItem<'_>> {
+    let indent = indentation(line);
+    let trimmed = line.trim_start();
+
+    let (number, kind, marker_len) = match trimmed.chars().next()? {
+        c @ ('-'|'*'|'+') => (None, c, 1),
+        c if c.is_ascii_digit() => {
+            let digits = trimmed.len() - trimmed.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            let kind = trimmed[digits..].chars().next().filter(|k| *k == '.' || *k == ')')?;
+            if digits > 9 {
+                return None;
+            }
+            (trimmed[..digits].parse().ok(), kind, digits + 1)
+        },
+        _ => return None,
+    };
+
+    let rest = &trimmed[marker_len..];
+    if !rest.is_empty() && !rest.starts_with([ ' ', '\t', ]) {
+        return None;
+    }
+
+    let spaces = indentation(rest);
+    let content = rest.trim_start();
+    let content_indent = if content.is_empty() || spaces > 4 {
+        indent + marker_len + 1
+    } else {
+        indent + marker_len + spaces
+    };
+
+    Some(ListItem { indent, kind, number, content_indent, content, })
+}
+
+fn footnote_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[^")?;
+    let end = rest.find("]:")?;
+    let label = &rest[..end];
+    if label.is_empty() || label.contains([ ' ', '[', ']', ]) {
+        return None;
+    }
+
+    Some((label, rest[end + 2..].trim()))
+}
+
+/// Normalizes the label of a link reference for comparison.
+fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// The label and destination of a link reference definition, and whether it
+/// has a title.
+fn reference_definition(line: &str) -> Option<(String, String, bool)> {
+    if indentation(line) > 3 {
+        return None;
+    }
+
+    let rest = line.trim().strip_prefix('[')?;
+    if rest.starts_with('^') {
+        return None;
+    }
+
+    let end = rest.find("]:")?;
+    let label = &rest[..end];
+    if label.trim().is_empty() {
+        return None;
+    }
+
+    let rest = rest[end + 2..].trim();
+    let (destination, title) = match rest.strip_prefix('<') {
+        Some(r) => {
+            let end = r.find('>')?;
+            (&r[..end], r[end + 1..].trim())
+        },
+        None => match rest.split_once([ ' ', '\t', ]) {
+            Some((d, t)) => (d, t.trim()),
+            None => (rest, ""),
+        },
+    };
+
+    if destination.is_empty() && !rest.starts_with('<') {
+        return None;
+    }
+
+    let titled = !title.is_empty();
+    if titled && !(
+        (title.starts_with('"') && title.ends_with('"')) ||
+        (title.starts_with('\'') && title.ends_with('\'')) ||
+        (title.starts_with('(') && title.ends_with(')'))
+    ) {
+        return None;
+    }
+
+    Some((normalize_label(label), destination.to_owned(), titled))
+}
+
+fn table_delimiter(line: &str) -> Option<Vec<&'static str>> {
+    let cells = split_cells(line);
+    if cells.is_empty() || !line.contains(['|', '-']) {
+        return None;
+    }
+
+    cells.iter().map(|cell| {
+        let cell = cell.trim();
+        let left = cell.starts_with(':');
+        let right = cell.ends_with(':');
+        let dashes = cell.trim_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+            return None;
+        }
+
+        Some(match (left, right) {
+            (true, true) => "",
+            (false, true) => "$",
+            _ => "^",
+        })
+    }).collect()
+}
+
+/// Splits a row of a table into its cells, keeping escaped pipes.
+fn split_cells(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = if line.ends_with('|') && !line.ends_with("\\|") {
+        &line[..line.len() - 1]
+    } else {
+        line
+    };
+
+    let mut cells = vec![ String::new(), ];
+    let mut escaped = false;
+    for c in line.chars() {
+        if c == '|' && !escaped {
+            cells.push(String::new());
+        } else {
+            cells.last_mut().unwrap().push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+
+    cells.into_iter().map(|c| c.trim().to_owned()).collect()
+}
+
+struct ListLevel {
+    indent: usize,
+    kind: char,
+    content_indent: usize,
+}
+
+struct Converter<'a> {
+    lines: Vec<&'a str>,
+    index: usize,
+    output: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    references: HashMap<String, String>,
+    /// The labels of the footnotes which are defined.
+    footnotes: HashSet<String>,
+    lists: Vec<ListLevel>,
+}
+
+impl<'a> Converter<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            lines: input.lines().collect(),
+            index: 0,
+            output: Vec::new(),
+            diagnostics: Vec::new(),
+            references: HashMap::new(),
+            footnotes: HashSet::new(),
+            lists: Vec::new(),
+        }
+    }
+
+    fn diagnostic(&mut self, line: usize, message: &str) {
+        self.diagnostics.push(Diagnostic { line: line + 1, message: message.to_owned(), });
+    }
+
+    fn line(&self) -> &'a str {
+        self.lines.get(self.index).copied().unwrap_or_default()
+    }
+
+    fn push_blank(&mut self) {
+        if self.output.last().is_some_and(|l| !l.is_empty()) {
+            self.output.push(String::new());
+        }
+    }
+
+    /// Whether a line ends a paragraph.
+    fn interrupts_paragraph(&self, line: &str) -> bool {
+        is_blank(line) || atx_heading(line).is_some() || thematic_break(line) ||
+            fence_open(line).is_some() || blockquote_line(line).is_some() ||
+            html_block_start(line) ||
+            list_item(line).is_some_and(|i| {
+                !i.content.is_empty() && i.number.is_none_or(|n| n == 1)
+            })
+    }
+
+    /// Collects the link reference definitions, which are removed from the
+    /// document and written into the links which use them.
+    fn collect_references(&mut self) -> Vec<bool> {
+        let mut definitions = vec![ false; self.lines.len() ];
+        let mut fence = None;
+        let mut paragraph = false;
+
+        for (i, definition) in definitions.iter_mut().enumerate() {
+            let line = self.lines[i];
+            if let Some((c, len)) = fence {
+                if fence_close(line, c, len) {
+                    fence = None;
+                }
+                continue;
+            }
+
+            if let Some((c, len, _, _)) = fence_open(line) {
+                fence = Some((c, len));
+                continue;
+            }
+
+            if let Some((label, _)) = footnote_definition(line) {
+                self.footnotes.insert(label.to_owned());
+            }
+
+            if !paragraph {
+                if let Some((label, destination, titled)) = reference_definition(line) {
+                    *definition = true;
+                    if titled {
+                        self.diagnostic(i, "link titles have no equivalent and were removed");
+                    }
+                    self.references.entry(label).or_insert(destination);
+                    continue;
+                }
+            }
+
+            paragraph = !is_blank(line) && indentation(line) < 4;
+        }
+
+        definitions
+    }
+
+    fn convert(&mut self) {
+        let definitions = self.collect_references();
+
+        while self.index < self.lines.len() {
+            if definitions[self.index] {
+                self.index += 1;
+                continue;
+            }
+
+            if !self.lists.is_empty() {
+                self.list_line();
+                continue;
+            }
+
+            self.block();
+        }
+    }
+
+    /// Converts the block beginning on the current line.
+    fn block(&mut self) {
+        let line = self.line();
+
+        if is_blank(line) {
+            self.push_blank();
+            self.index += 1;
+        } else if let Some((c, len, indent, info)) = fence_open(line) {
+            self.fenced_code(c, len, indent, info);
+        } else if indentation(line) >= 4 {
+            self.indented_code();
+        } else if let Some((level, text)) = atx_heading(line) {
+            let text = self.inline(text, self.index);
+            self.output.push(format!("{} {}", "#".repeat(level), text).trim_end().to_owned());
+            self.index += 1;
+        } else if thematic_break(line) {
+            self.output.push("- - -".to_owned());
+            self.index += 1;
+        } else if blockquote_line(line).is_some() {
+            self.blockquote();
+        } else if html_block_start(line) {
+            self.diagnostic(self.index, "html blocks have no equivalent and were removed");
+            while self.index < self.lines.len() && !is_blank(self.line()) {
+                self.index += 1;
+            }
+        } else if let Some(item) = list_item(line) {
+            self.push_blank();
+            self.list_item(item);
+        } else if let Some((label, text)) = footnote_definition(line) {
+            self.footnote_definition(label, text);
+        } else if self.lines.get(self.index + 1).and_then(|l| table_delimiter(l))
+            .is_some_and(|d| d.len() == split_cells(line).len() && line.contains('|'))
+        {
+            self.table();
+        } else {
+            self.paragraph();
+        }
+    }
+
+    fn fenced_code(&mut self, c: char, len: usize, indent: usize, info: &str) {
+        let start = self.index;
+        let lang = info.split_whitespace().next().unwrap_or_default();
+        self.output.push(format!("```{lang}"));
+        self.index += 1;
+
+        while self.index < self.lines.len() && !fence_close(self.line(), c, len) {
+            let line = unindent(self.line(), indent);
+            if line.starts_with("```") {
+                self.diagnostic(self.index, "a line of code beginning with three backticks cannot be written within a code fence");
+            }
+            self.output.push(line.to_owned());
+            self.index += 1;
+        }
+
+        if self.index >= self.lines.len() {
+            self.diagnostic(start, "unclosed code fence was closed at the end of the document");
+        }
+
+        self.output.push("```".to_owned());
+        self.index += 1;
+    }
+
+    /// Indented code is written as a fence, which unlike a code block may
+    /// contain empty lines.
+    fn indented_code(&mut self) {
+        let mut lines = Vec::new();
+        while self.index < self.lines.len() {
+            let line = self.line();
+            if is_blank(line) {
+                // empty lines only belong to the block when more code follows
+                let more = self.lines[self.index..].iter()
+                    .find(|l| !is_blank(l))
+                    .is_some_and(|l| indentation(l) >= 4);
+                if !more {
+                    break;
+                }
+                lines.push(String::new());
+            } else if indentation(line) >= 4 {
+                if unindent(line, 4).starts_with("```") {
+                    self.diagnostic(self.index, "a line of code beginning with three backticks cannot be written within a code fence");
+                }
+                lines.push(unindent(line, 4).to_owned());
+            } else {
+                break;
+            }
+            self.index += 1;
+        }
+
+        self.output.push("```".to_owned());
+        self.output.extend(lines);
+        self.output.push("```".to_owned());
+    }
+
+    fn blockquote(&mut self) {
+        let start = self.index;
+        let mut lines = Vec::new();
+
+        while self.index < self.lines.len() {
+            let line = self.line();
+            match blockquote_line(line) {
+                Some(content) => lines.push(content),
+                // lazy continuation of a paragraph within the quote
+                None if lines.last().is_some_and(|l| !is_blank(l)) &&
+                    !self.interrupts_paragraph(line) => lines.push(line.trim_start()),
+                None => break,
+            }
+            self.index += 1;
+        }
+
+        let nested = lines.iter().any(|l| {
+            blockquote_line(l).is_some() || list_item(l).is_some_and(|i| !i.content.is_empty()) ||
+                atx_heading(l).is_some() || fence_open(l).is_some()
+        });
+        if nested {
+            self.diagnostic(start, "blockquotes may only contain text; nested blocks were written as text");
+        }
+
+        let text = lines.iter()
+            .map(|l| l.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = self.inline(&text, start);
+
+        for line in text.split('\n') {
+            if line.is_empty() {
+                self.output.push(">".to_owned());
+            } else {
+                self.output.push(format!("> {line}"));
+            }
+        }
+    }
+
+    fn footnote_definition(&mut self, label: &str, text: &str) {
+        let start = self.index;
+        let mut lines = vec![ text, ];
+        self.index += 1;
+
+        while self.index < self.lines.len() {
+            let line = self.line();
+            if is_blank(line) {
+                let more = self.lines.get(self.index + 1).is_some_and(|l| indentation(l) >= 4);
+                if !more {
+                    break;
+                }
+                self.diagnostic(self.index, "paragraphs of a footnote were joined into one");
+            } else if indentation(line) >= 4 || !self.interrupts_paragraph(line) {
+                lines.push(line.trim());
+            } else {
+                break;
+            }
+            self.index += 1;
+        }
+
+        let text = self.inline(&lines.join("\n"), start);
+        self.output.push(format!("[^{label}]: {text}"));
+    }
+
+    fn table(&mut self) {
+        let alignments = table_delimiter(self.lines[self.index + 1]).unwrap_or_default();
+
+        let header = split_cells(self.line());
+        let mut row = Vec::new();
+        for (cell, align) in header.iter().zip(&alignments) {
+            let cell = self.inline(cell, self.index);
+            row.push(format!("|={align} {cell}").trim_end().to_owned());
+        }
+        self.output.push(row.join(" "));
+        self.index += 2;
+
+        while self.index < self.lines.len() {
+            let line = self.line();
+            if is_blank(line) || (self.interrupts_paragraph(line) && !line.contains('|')) {
+                break;
+            }
+
+            let mut row = Vec::new();
+            let cells = split_cells(line);
+            for (i, align) in alignments.iter().enumerate() {
+                let cell = cells.get(i).map(String::as_str).unwrap_or_default();
+                let cell = self.inline(cell, self.index);
+                let flags = if align.is_empty() { "_" } else { align };
+                row.push(format!("|{flags} {cell}").trim_end().to_owned());
+            }
+
+            if cells.len() > alignments.len() {
+                self.diagnostic(self.index, "cells beyond those of the header row were removed");
+            }
+
+            self.output.push(row.join(" "));
+            self.index += 1;
+        }
+    }
+
+    fn paragraph(&mut self) {
+        let start = self.index;
+        let mut lines = vec![ self.line().trim_start(), ];
+        self.index += 1;
+
+        while self.index < self.lines.len() {
+            let line = self.line();
+            if let Some(level) = setext_underline(line) {
+                let text = lines.iter()
+                    .map(|l| l.trim())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let text = self.inline(&text, start);
+                self.output.push(format!("{} {text}", "#".repeat(level)));
+                self.index += 1;
+                return;
+            }
+
+            if self.interrupts_paragraph(line) {
+                break;
+            }
+
+            lines.push(line.trim_start());
+            self.index += 1;
+        }
+
+        let text = self.inline(&paragraph_text(&lines), start);
+        let mut text = text.split('\n');
+        let first = text.next().unwrap_or_default();
+
+        // a paragraph may not begin with the markers of another block
+        if first.starts_with(['#', '>']) || first.starts_with("- ") || first.starts_with("```") {
+            self.diagnostic(start, "a paragraph beginning with the marker of another block would become that block");
+        }
+
+        // a backslash keeps a leading number such as `3r.` from beginning an
+        // ordered list, as it keeps a pipe from beginning a table
+        if first.starts_with('|') || ordered_marker(first) {
+            self.output.push(format!("\\{first}"));
+        } else {
+            self.output.push(first.to_owned());
+        }
+        self.output.extend(text.map(str::to_owned));
+    }
+
+    /// Handles a line while within a list.
+    fn list_line(&mut self) {
+        let line = self.line();
+
+        if is_blank(line) {
+            // the list continues past empty lines when an item or more
+            // content of an item follows
+            let next = self.lines[self.index..].iter().find(|l| !is_blank(l));
+            let continues = next.is_some_and(|l| {
+                list_item(l).is_some_and(|i| i.indent >= self.lists[0].indent && i.indent < self.lists[0].indent + 4) ||
+                    list_item(l).is_some_and(|i| i.indent >= self.lists[0].content_indent) ||
+                    indentation(l) >= self.lists[0].content_indent
+            });
+
+            if !continues {
+                self.lists.clear();
+                self.push_blank();
+                self.index += 1;
+                return;
+            }
+
+            let next = next.unwrap();
+            if list_item(next).is_none() && indentation(next) >= self.lists[0].content_indent {
+                self.diagnostic(self.index, "paragraphs of a list item were joined into one");
+            }
+
+            self.index += 1;
+            return;
+        }
+
+        let item = list_item(line).filter(|i| {
+            i.indent >= self.lists[0].indent && !thematic_break(line)
+        });
+
+        if let Some(item) = item {
+            self.list_item(item);
+            return;
+        }
+
+        let indent = indentation(line);
+        let previous_blank = self.index > 0 && is_blank(self.lines[self.index - 1]);
+        let continues = indent >= self.lists.last().unwrap().content_indent ||
+            (!previous_blank && !self.interrupts_paragraph(line)) ||
+            (previous_blank && indent >= self.lists[0].content_indent);
+
+        if !continues {
+            self.lists.clear();
+            self.push_blank();
+            return;
+        }
+
+        if fence_open(line.trim_start()).is_some() || indent >= self.lists.last().unwrap().content_indent + 4 {
+            self.diagnostic(self.index, "code within a list item has no equivalent and was written as text");
+        }
+
+        let depth = self.lists.len() - 1;
+        let text = self.inline(line.trim(), self.index);
+        self.output.push(format!("{}  {text}", "    ".repeat(depth)));
+        self.index += 1;
+    }
+
+    fn list_item(&mut self, item: ListItem<'_>) {
+        let mut first = false;
+
+        // find the list the item belongs to, closing any nested deeper
+        loop {
+            match self.lists.last() {
+                Some(level) if item.indent >= level.content_indent => {
+                    first = true;
+                    break;
+                },
+                Some(level) if item.indent >= level.indent => {
+                    // a marker of another kind begins another list
+                    if level.kind != item.kind {
+                        if self.lists.len() == 1 {
+                            self.output.push(String::new());
+                            first = true;
+                        } else {
+                            self.diagnostic(self.index, "nested lists of different kinds at the same level are merged");
+                        }
+                    }
+
+                    self.lists.pop();
+                    break;
+                },
+                Some(_) => {
+                    self.lists.pop();
+                },
+                None => {
+                    first = true;
+                    break;
+                },
+            }
+        }
+
+        if !first && self.lists.is_empty() {
+            first = self.output.last().is_none_or(|l| l.is_empty());
+        }
+
+        let depth = self.lists.len();
+        self.lists.push(ListLevel {
+            indent: item.indent,
+            kind: item.kind,
+            content_indent: item.content_indent,
+        });
+
+        let marker = match item.number {
+            Some(n) if first && n > 1 => format!("{n}."),
+            Some(_) => "0.".to_owned(),
+            None => "-".to_owned(),
+        };
+
+        let mut content = item.content;
+        let mut checkbox = "";
+        for (task, replacement) in [ ("[ ] ", "[ ] "), ("[x] ", "[x] "), ("[X] ", "[x] "), ] {
+            if let Some(rest) = content.strip_prefix(task) {
+                content = rest;
+                checkbox = replacement;
+            }
+        }
+
+        if fence_open(content).is_some() {
+            self.diagnostic(self.index, "code within a list item has no equivalent and was written as text");
+        }
+
+        let text = self.inline(content, self.index);
+        self.output.push(format!("{}{marker} {checkbox}{text}", "    ".repeat(depth)).trim_end().to_owned());
+        self.index += 1;
+    }
+
+    fn inline(&mut self, text: &str, line: usize) -> String {
+        let mut inline = Inline {
+            chars: text.chars().collect(),
+            references: &self.references,
+            footnotes: &self.footnotes,
+            diagnostics: Vec::new(),
+            line,
+        };
+
+        let converted = inline.convert(0, inline.chars.len());
+        self.diagnostics.extend(inline.diagnostics);
+        converted
+    }
+}
+
+/// Joins the lines of a paragraph, keeping hard line breaks made of trailing
+/// spaces.
+/// Whether a line begins with the marker of a No-Flavor Markdown ordered list,
+/// such as `3.`, `1a.` or `3r.`, which CommonMark reads as text.
+fn ordered_marker(line: &str) -> bool {
+    let rest = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    if rest.len() == line.len() {
+        return false;
+    }
+
+    let rest = rest.strip_prefix(['a', 'A', 'i', 'I']).unwrap_or(rest);
+    let rest = rest.strip_prefix('r').unwrap_or(rest);
+    rest.strip_prefix('.').is_some_and(|r| r.is_empty() || r.starts_with(' '))
+}
+
+fn paragraph_text(lines: &[&str]) -> String {
+    let last = lines.len().saturating_sub(1);
+    lines.iter().enumerate().map(|(i, l)| {
+        if i != last && l.ends_with("  ") {
+            format!("{}  ", l.trim_end())
+        } else {
+            l.trim_end().to_owned()
+        }
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// A piece of converted inline content.
+enum Token {
+    /// Written as is.
+    Text(String),
+    /// A run of delimiters which may open or close emphasis.
+    Delimiter {
+        c: char,
+        count: usize,
+        can_open: bool,
+        can_close: bool,
+        /// Markers written after the remaining delimiters, when the run opens.
+        opens: Vec<&'static str>,
+        /// Markers written before the remaining delimiters, when the run closes.
+        closes: Vec<&'static str>,
+    },
+}
+
+/// Escapes the characters of text which No-Flavor Markdown would read as
+/// markup.
+fn escape(c: char, next: Option<char>, output: &mut String) {
+    match (c, next) {
+        ('\\'|'*'|'_'|'~'|'`'|'^'|'<', _) => output.push('\\'),
+        ('+', Some('+'))|('=', Some('=')) => output.push('\\'),
+        ('[', Some(' '|'x'|'X'|'^')) => output.push('\\'),
+        _ => {},
+    }
+    output.push(c);
+}
+
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || (!c.is_alphanumeric() && !c.is_whitespace())
+}
+
+struct Inline<'r> {
+    chars: Vec<char>,
+    references: &'r HashMap<String, String>,
+    footnotes: &'r HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+    line: usize,
+}
+
+impl Inline<'_> {
+    fn diagnostic(&mut self, at: usize, message: String) {
+        let line = self.line + self.chars[..at].iter().filter(|c| **c == '\n').count();
+        self.diagnostics.push(Diagnostic { line: line + 1, message, });
+    }
+
+    fn text(&self, start: usize, end: usize) -> String {
+        self.chars[start..end].iter().collect()
+    }
+
+    /// Finds the closing run of backticks of a code span.
+    fn code_close(&self, start: usize, end: usize, len: usize) -> Option<usize> {
+        let mut i = start;
+        while i < end {
+            if self.chars[i] == '`' {
+                let run = self.chars[i..end].iter().take_while(|c| **c == '`').count();
+                if run == len {
+                    return Some(i);
+                }
+                i += run;
+            } else {
+                i += 1;
+            }
+        }
+        None
+    }
+
+    /// Finds the closing bracket of link text.
+    fn bracket_close(&self, start: usize, end: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut i = start;
+        while i < end {
+            match self.chars[i] {
+                '\\' => i += 1,
+                '`' => {
+                    let run = self.chars[i..end].iter().take_while(|c| **c == '`').count();
+                    if let Some(close) = self.code_close(i + run, end, run) {
+                        i = close + run - 1;
+                    } else {
+                        i += run - 1;
+                    }
+                },
+                '[' => depth += 1,
+                ']' if depth == 0 => return Some(i),
+                ']' => depth -= 1,
+                _ => {},
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Reads the destination and title of an inline link, beginning after
+    /// the opening parenthesis, returning them along with the position
+    /// after the closing parenthesis.
+    fn link_destination(&self, start: usize, end: usize) -> Option<(String, bool, usize)> {
+        let mut i = start;
+        let skip_spaces = |mut i: usize| {
+            while i < end && self.chars[i].is_whitespace() {
+                i += 1;
+            }
+            i
+        };
+
+        i = skip_spaces(i);
+        let mut destination = String::new();
+        if self.chars.get(i) == Some(&'<') {
+            i += 1;
+            while i < end && self.chars[i] != '>' {
+                if self.chars[i] == '\n' {
+                    return None;
+                }
+                destination.push(self.chars[i]);
+                i += 1;
+            }
+            if i >= end {
+                return None;
+            }
+            i += 1;
+        } else {
+            let mut depth = 0;
+            while i < end && !self.chars[i].is_whitespace() {
+                match self.chars[i] {
+                    '\\' if i + 1 < end && self.chars[i + 1].is_ascii_punctuation() => {
+                        i += 1;
+                    },
+                    '(' => depth += 1,
+                    ')' if depth == 0 => break,
+                    ')' => depth -= 1,
+                    _ => {},
+                }
+                destination.push(self.chars[i]);
+                i += 1;
+            }
+        }
+
+        i = skip_spaces(i);
+        let mut titled = false;
+        if let Some(open) = self.chars.get(i).filter(|c| matches!(c, '"'|'\''|'(')) {
+            let close = if *open == '(' { ')' } else { *open };
+            i += 1;
+            while i < end && self.chars[i] != close {
+                i += 1;
+            }
+            if i >= end {
+                return None;
+            }
+            titled = true;
+            i = skip_spaces(i + 1);
+        }
+
+        if self.chars.get(i) != Some(&')') {
+            return None;
+        }
+
+        Some((destination, titled, i + 1))
+    }
+
+    /// Converts a link or image beginning at the bracket, returning the
+    /// converted link and the position after it.
+    fn link(&mut self, open: usize, end: usize, image: bool) -> Option<(String, usize)> {
+        let close = self.bracket_close(open + 1, end)?;
+        let label = self.text(open + 1, close);
+
+        // footnote references are written the same way, while those without
+        // a definition are text
+        if !image && label.starts_with('^') && !label.contains(char::is_whitespace) {
+            if !self.footnotes.contains(&label[1..]) {
+                return None;
+            }
+            return Some((format!("[{label}]"), close + 1));
+        }
+
+        let (destination, after) = match self.chars.get(close + 1) {
+            Some('(') => {
+                let (destination, titled, after) = self.link_destination(close + 2, end)?;
+                if titled {
+                    self.diagnostic(open, "link titles have no equivalent and were removed".to_owned());
+                }
+                (destination, after)
+            },
+            Some('[') => {
+                let ref_close = self.bracket_close(close + 2, end)?;
+                let reference = self.text(close + 2, ref_close);
+                let reference = if reference.trim().is_empty() { &label } else { &reference };
+                (self.references.get(&normalize_label(reference))?.clone(), ref_close + 1)
+            },
+            _ => (self.references.get(&normalize_label(&label))?.clone(), close + 1),
+        };
+
+        if destination.contains(')') || destination.contains(char::is_whitespace) {
+            self.diagnostic(open, format!(
+                "the link destination \"{destination}\" cannot contain spaces or parentheses",
+            ));
+        }
+
+        let text = self.convert(open + 1, close);
+        if text.contains(']') {
+            self.diagnostic(open, "link text cannot contain a closing bracket".to_owned());
+        }
+
+        let bang = if image { "!" } else { "" };
+        Some((format!("{bang}[{text}]({destination})"), after))
+    }
+
+    /// Converts an autolink or inline html beginning at the angle bracket,
+    /// returning the converted markup and the position after it.
+    fn angle_bracket(&mut self, open: usize, end: usize) -> Option<(String, usize)> {
+        let close = (open + 1..end).find(|i| self.chars[*i] == '>')?;
+        let inner = self.text(open + 1, close);
+
+        if inner.starts_with("!--") {
+            self.diagnostic(open, "html comments have no equivalent and were removed".to_owned());
+            let text = self.text(open, end);
+            let len = text.find("-->").map(|i| text[..i].chars().count() + 3)?;
+            return Some((String::new(), open + len));
+        }
+
+        if inner.contains(char::is_whitespace) && !inner.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        // autolinks
+        let scheme = inner.split_once(':').map(|(s, _)| s).unwrap_or_default();
+        if (2..=32).contains(&scheme.len()) &&
+            scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+'|'.'|'-')) &&
+            !inner.contains(char::is_whitespace)
+        {
+            return Some((format!("[{inner}]({inner})"), close + 1));
+        }
+
+        if inner.contains('@') && !inner.contains(char::is_whitespace) && !inner.contains(':') {
+            return Some((format!("[{inner}](mailto:{inner})"), close + 1));
+        }
+
+        // inline html with an equivalent
+        let name = inner.trim_start_matches('/')
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if name.is_empty() || !inner.trim_start_matches('/').starts_with(|c: char| c.is_ascii_alphabetic()) {
+            return None;
+        }
+
+        let markup = match name.as_str() {
+            "ins"|"u" => "++",
+            "mark" => "==",
+            "sup" => "^",
+            "del"|"s"|"strike" => "~~",
+            "strong"|"b" => "**",
+            "em"|"i" => "_",
+            "code" => "`",
+            "br" => "  \n",
+            _ => {
+                if !inner.starts_with('/') {
+                    self.diagnostic(open, format!("inline html <{name}> has no equivalent and was removed"));
+                }
+                ""
+            },
+        };
+
+        Some((markup.to_owned(), close + 1))
+    }
+
+    fn delimiter(&self, i: usize, end: usize) -> Token {
+        let c = self.chars[i];
+        let count = self.chars[i..end].iter().take_while(|x| **x == c).count();
+        let before = if i == 0 { ' ' } else { self.chars[i - 1] };
+        let after = self.chars.get(i + count).filter(|_| i + count < end).copied().unwrap_or(' ');
+
+        let left = !after.is_whitespace() &&
+            (!is_punctuation(after) || before.is_whitespace() || is_punctuation(before));
+        let right = !before.is_whitespace() &&
+            (!is_punctuation(before) || after.is_whitespace() || is_punctuation(after));
+
+        let (can_open, can_close) = match c {
+            '_' => (
+                left && (!right || is_punctuation(before)),
+                right && (!left || is_punctuation(after)),
+            ),
+            _ => (left, right),
+        };
+
+        Token::Delimiter {
+            c,
+            count,
+            can_open,
+            can_close,
+            opens: Vec::new(),
+            closes: Vec::new(),
+        }
+    }
+
+    /// Converts the inline content between two positions.
+    fn convert(&mut self, start: usize, end: usize) -> String {
+        let mut tokens = Vec::new();
+        let mut text = String::new();
+        let mut i = start;
+
+        macro_rules! push_token {
+            ($token:expr) => {
+                if !text.is_empty() {
+                    tokens.push(Token::Text(std::mem::take(&mut text)));
+                }
+                tokens.push($token);
+            }
+        }
+
+        while i < end {
+            let c = self.chars[i];
+            let next = self.chars.get(i + 1).filter(|_| i + 1 < end).copied();
+
+            match c {
+                '\\' if next == Some('\n') => {
+                    text.push_str("  ");
+                    i += 1;
+                },
+                // escaped pipes only appear within tables, where they remain
+                '\\' if next == Some('|') => {
+                    text.push_str("\\|");
+                    i += 2;
+                },
+                '\\' if next.is_some_and(|n| n.is_ascii_punctuation()) => {
+                    escape(next.unwrap(), self.chars.get(i + 2).copied(), &mut text);
+                    i += 2;
+                },
+                '`' => {
+                    let run = self.chars[i..end].iter().take_while(|c| **c == '`').count();
+                    match self.code_close(i + run, end, run) {
+                        Some(close) => {
+                            let mut code = self.text(i + run, close).replace('\n', " ");
+                            if code.len() > 2 && code.starts_with(' ') && code.ends_with(' ') &&
+                                !code.trim().is_empty()
+                            {
+                                code = code[1..code.len() - 1].to_owned();
+                            }
+
+                            // backslashes and backticks are escapes within code
+                            let code = code.replace('\\', "\\\\").replace('`', "\\`");
+                            text.push('`');
+                            text.push_str(&code);
+                            text.push('`');
+                            i = close + run;
+                        },
+                        None => {
+                            for _ in 0..run {
+                                text.push_str("\\`");
+                            }
+                            i += run;
+                        },
+                    }
+                },
+                '!' if next == Some('[') => match self.link(i + 1, end, true) {
+                    Some((link, after)) => {
+                        text.push_str(&link);
+                        i = after;
+                    },
+                    None => {
+                        text.push('!');
+                        i += 1;
+                    },
+                },
+                '[' => match self.link(i, end, false) {
+                    Some((link, after)) => {
+                        text.push_str(&link);
+                        i = after;
+                    },
+                    None => {
+                        escape(c, next, &mut text);
+                        i += 1;
+                    },
+                },
+                '<' => match self.angle_bracket(i, end) {
+                    Some((markup, after)) => {
+                        text.push_str(&markup);
+                        i = after;
+                    },
+                    None => {
+                        text.push_str("\\<");
+                        i += 1;
+                    },
+                },
+                '*'|'_'|'~' => {
+                    let token = self.delimiter(i, end);
+                    if let Token::Delimiter { count, .. } = &token {
+                        i += count;
+                    }
+                    push_token!(token);
+                },
+                _ => {
+                    escape(c, next, &mut text);
+                    i += 1;
+                },
+            }
+        }
+
+        if !text.is_empty() {
+            tokens.push(Token::Text(text));
+        }
+
+        resolve_emphasis(&mut tokens);
+
+        let mut output = String::new();
+        for token in tokens {
+            match token {
+                Token::Text(t) => output.push_str(&t),
+                Token::Delimiter { c, count, opens, closes, .. } => {
+                    for marker in closes {
+                        output.push_str(marker);
+                    }
+                    for _ in 0..count {
+                        output.push('\\');
+                        output.push(c);
+                    }
+                    for marker in opens.iter().rev() {
+                        output.push_str(marker);
+                    }
+                },
+            }
+        }
+
+        output
+    }
+}
+
+/// Pairs runs of delimiters into emphasis, much like CommonMark does.
+fn resolve_emphasis(tokens: &mut [Token]) {
+    for closer in 0..tokens.len() {
+        while let Token::Delimiter { c, count, can_close: true, .. } = tokens[closer] {
+            if count == 0 {
+                break;
+            }
+
+            let opener = (0..closer).rev().find(|j| matches!(
+                tokens[*j],
+                Token::Delimiter { c: o, count: n, can_open: true, .. } if o == c && n > 0
+            ));
+
+            let Some(opener) = opener else {
+                break;
+            };
+
+            let Token::Delimiter { count: open_count, .. } = tokens[opener] else {
+                break;
+            };
+
+            let (used, marker) = match c {
+                // deleted text is marked by one or two tildes on either side
+                '~' if open_count != count => break,
+                '~' => (count, "~~"),
+                _ if open_count >= 2 && count >= 2 => (2, "**"),
+                _ => (1, "_"),
+            };
+
+            if let Token::Delimiter { count, opens, .. } = &mut tokens[opener] {
+                *count -= used;
+                opens.push(marker);
+            }
+
+            if let Token::Delimiter { count, closes, .. } = &mut tokens[closer] {
+                *count -= used;
+                closes.push(marker);
+            }
+
+            // delimiters between the pair can no longer be paired
+            for token in &mut tokens[opener + 1..closer] {
+                if let Token::Delimiter { can_open, .. } = token {
+                    *can_open = false;
+                }
+            }
+        }
+    }
+}
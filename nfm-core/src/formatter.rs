@@ -0,0 +1,852 @@
+// nfm-core::formatter: Writes No-Flavor Markdown in a canonical form.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Writes No-Flavor Markdown in a canonical form.
+//!
+//! The formatter reads blocks the same way as the parser, line by line, so a
+//! formatted document parses into the same html as the original.
+
+/// Options for formatting a document.
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// The column at which the text of paragraphs, blockquotes and list items
+    /// is wrapped. Text is left as it was written when there is none.
+    pub width: Option<usize>,
+}
+
+/// Formats a document, returning the canonical form of it.
+pub fn format(input: &str) -> String {
+    format_with(input, FormatOptions::default())
+}
+
+/// Formats a document with options, returning the canonical form of it.
+pub fn format_with(input: &str, options: FormatOptions) -> String {
+    let mut formatter = Formatter {
+        width: options.width,
+        ..Default::default()
+    };
+    let mut lines = input.lines();
+
+    // the metadata block is kept as it was written
+    let mut rest = lines.clone();
+    if rest.next().map(str::trim_end) == Some("---") && crate::closes_metadata(rest) {
+        formatter.lines.push("---".to_owned());
+        lines.next();
+        for line in lines.by_ref() {
+            let line = line.trim_end();
+            formatter.lines.push(line.to_owned());
+            if line == "---" {
+                break;
+            }
+        }
+    }
+
+    for line in lines {
+        formatter.line(line);
+    }
+
+    formatter.write_table();
+    formatter.write_text();
+    while formatter.lines.last().is_some_and(String::is_empty) {
+        formatter.lines.pop();
+    }
+
+    let mut output = formatter.lines.join("\n");
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+/// The block being read, only one of which may be open at a time.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Block {
+    #[default]
+    None,
+    Paragraph,
+    List,
+    PreCode,
+    Fence,
+    Blockquote,
+    Table,
+}
+
+/// The text of a paragraph, blockquote or list item, which is written once
+/// the block ends so that it may be wrapped.
+#[derive(Default)]
+struct Text {
+    block: Block,
+    /// The prefix of the first line.
+    first: String,
+    /// The prefix of every other line.
+    rest: String,
+    lines: Vec<String>,
+}
+
+impl Text {
+    fn new(block: Block, first: String, rest: String) -> Self {
+        Self { block, first, rest, lines: Vec::new(), }
+    }
+
+    /// Writes the lines of the text as they were read.
+    fn lines(&self) -> Vec<String> {
+        self.lines.iter()
+            .enumerate()
+            .map(|(i, line)| prefixed(if i == 0 { &self.first } else { &self.rest }, line))
+            .collect()
+    }
+
+    /// Whether a piece of text may begin a line other than the first without
+    /// beginning a new block.
+    fn may_begin_line(&self, piece: &str) -> bool {
+        match self.block {
+            Block::List => list_marker(piece).is_none(),
+            Block::Paragraph => !(piece.starts_with('\\') &&
+                piece[1..].starts_with(|c: char| "#->|`".contains(c) || c.is_ascii_digit())),
+            _ => true,
+        }
+    }
+
+    /// Wraps the text at a width, keeping the line breaks of the text. A line
+    /// is never broken within a link, inline code or an anchor, and is always
+    /// broken after a link or anchor left open, which could otherwise be
+    /// closed by the text that follows it.
+    fn wrap(&self, width: usize) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = self.first.clone();
+        let mut empty = true;
+
+        macro_rules! break_line {
+            () => {
+                if !empty {
+                    lines.push(std::mem::replace(&mut line, self.rest.clone()));
+                    empty = true;
+                }
+            }
+        }
+
+        for text in &self.lines {
+            if text.trim().is_empty() {
+                // the empty lines of a blockquote separate its text
+                if self.block == Block::Blockquote {
+                    break_line!();
+                    lines.push(prefixed(&self.rest, text));
+                    line = self.rest.clone();
+                }
+                continue;
+            }
+
+            let pieces = pieces(text.trim_end());
+            for (i, (piece, open)) in pieces.iter().enumerate() {
+                let len = line.chars().count() + 1 + piece.chars().count();
+                if len > width && self.may_begin_line(piece) {
+                    break_line!();
+                }
+
+                if !empty {
+                    line.push(' ');
+                }
+                line.push_str(piece);
+                empty = false;
+
+                if i + 1 == pieces.len() && text.ends_with("  ") {
+                    line.push_str("  ");
+                    break_line!();
+                } else if *open {
+                    break_line!();
+                }
+            }
+        }
+
+        // a list item may be empty
+        if !empty || lines.is_empty() {
+            lines.push(line.trim_end().to_owned());
+        }
+        lines
+    }
+}
+
+/// Prefixes a line of text, leaving only the prefix when the line is empty.
+fn prefixed(prefix: &str, text: &str) -> String {
+    match text {
+        "" => prefix.trim_end().to_owned(),
+        "  " => format!("{}  ", prefix.trim_end()),
+        text => format!("{prefix}{text}"),
+    }
+}
+
+#[derive(Default)]
+struct Formatter<'a> {
+    lines: Vec<String>,
+    width: Option<usize>,
+    block: Block,
+    /// Whether each open level of list is ordered.
+    list: Vec<bool>,
+    /// The text of the block being read.
+    text: Option<Text>,
+    /// The lines of the table being read.
+    table: Vec<&'a str>,
+}
+
+impl<'a> Formatter<'a> {
+    fn line(&mut self, line: &'a str) {
+        if self.block == Block::Fence {
+            if line.starts_with("```") {
+                self.lines.push("```".to_owned());
+                self.block = Block::None;
+            } else {
+                self.lines.push(line.to_owned());
+            }
+            return;
+        }
+
+        let trimmed = line.trim_start_matches(' ');
+
+        // the table is still open while the line is read, but is finished
+        if self.block == Block::Table && !trimmed.starts_with('|') {
+            self.write_table();
+        }
+
+        if line.trim().is_empty() {
+            self.block = Block::None;
+            self.list.clear();
+            self.write_text();
+            if self.lines.last().is_some_and(|l| !l.is_empty()) {
+                self.lines.push(String::new());
+            }
+        }
+        // heading
+        else if self.block == Block::None && line.starts_with('#') {
+            let hashes = line.len() - line.trim_start_matches('#').len();
+            let text = &line[hashes..];
+            if hashes <= 6 && (text.is_empty() || text.starts_with([' ', '\t'])) {
+                let text = text.trim_start();
+                let content = text.trim_end();
+                if content.is_empty() {
+                    self.push(line[..hashes].to_owned());
+                } else {
+                    // a heading ending in a break keeps the whitespace before it
+                    let end = if text.ends_with("  ") { &text[content.len()..] } else { "" };
+                    self.push(format!("{} {}{end}", &line[..hashes], escapes(content)));
+                }
+            } else {
+                self.push(line.trim_end().to_owned());
+            }
+        }
+        // hr
+        else if self.block == Block::None && line == "- - -" {
+            self.push(line.to_owned());
+        }
+        // list
+        else if matches!(self.block, Block::None|Block::List) && list_marker(line).is_some() {
+            self.list_item(line);
+        }
+        // pre-code
+        else if matches!(self.block, Block::None|Block::PreCode) && line.starts_with("    ") {
+            self.block = Block::PreCode;
+            self.push(line.to_owned());
+        }
+        // code fence
+        else if self.block == Block::None && line.starts_with("```") {
+            // the parser reads the language with the spaces around it
+            self.block = Block::Fence;
+            self.push(line.to_owned());
+        }
+        // blockquote
+        else if matches!(self.block, Block::None|Block::Blockquote) && line.starts_with('>') {
+            if self.block != Block::Blockquote {
+                self.block = Block::Blockquote;
+                self.begin_text(Text::new(Block::Blockquote, "> ".to_owned(), "> ".to_owned()));
+            }
+
+            // a line holding only a break
+            let text = if &line[1..] == "  " {
+                line[1..].to_owned()
+            } else {
+                inline(line[1..].trim_start_matches(' '))
+            };
+            self.push_text(text);
+        }
+        // table
+        else if (self.block == Block::None && line.starts_with('|')) ||
+            (self.block == Block::Table && trimmed.starts_with('|'))
+        {
+            self.block = Block::Table;
+            self.table.push(line);
+        }
+        // table of contents
+        else if self.block == Block::None && line.trim_end() == "[TOC]" {
+            self.push("[TOC]".to_owned());
+        }
+        // still within the same list item
+        else if self.block == Block::List {
+            self.push_text(inline(trimmed));
+        }
+        // paragraph
+        else {
+            let first = self.block != Block::Paragraph;
+            let text = paragraph_line(line, first);
+            if first {
+                self.block = Block::Paragraph;
+                self.list.clear();
+
+                // the indentation of the first line is kept
+                let content = text.trim_start_matches(' ');
+                let indent = text[..text.len() - content.len()].to_owned();
+                self.begin_text(Text::new(Block::Paragraph, indent, String::new()));
+                self.push_text(content.to_owned());
+            } else {
+                self.push_text(text);
+            }
+        }
+    }
+
+    fn list_item(&mut self, line: &str) {
+        let (marker, ordered) = list_marker(line).unwrap();
+        let text = inline(line.trim_start_matches(' ')[marker.len()..].trim_start_matches(' '));
+
+        // levels are counted in groups of four spaces, as the parser does
+        let level = (line.len() - line.trim_start_matches("    ").len()) / 4 + 1;
+
+        // a later item of an ordered list is only identified by its marker
+        self.list.truncate(level);
+        let marker = if self.list.len() == level && self.list[level - 1] && ordered {
+            "0."
+        } else {
+            marker
+        };
+        self.list.resize(level, ordered);
+        self.list[level - 1] = ordered;
+
+        let indent = " ".repeat((level - 1) * 4);
+        self.block = Block::List;
+        self.begin_text(Text::new(
+            Block::List,
+            format!("{indent}{marker} "),
+            " ".repeat(indent.len() + marker.len() + 1),
+        ));
+        self.push_text(text);
+    }
+
+    /// Writes a line which is not part of the text of a block.
+    fn push(&mut self, line: String) {
+        self.write_text();
+        self.lines.push(line);
+    }
+
+    fn begin_text(&mut self, text: Text) {
+        self.write_text();
+        self.text = Some(text);
+    }
+
+    fn push_text(&mut self, line: String) {
+        if let Some(text) = &mut self.text {
+            text.lines.push(line);
+        }
+    }
+
+    fn write_text(&mut self) {
+        if let Some(text) = self.text.take() {
+            match self.width {
+                Some(width) => self.lines.extend(text.wrap(width)),
+                None => self.lines.extend(text.lines()),
+            }
+        }
+    }
+
+    fn write_table(&mut self) {
+        if !self.table.is_empty() {
+            let table = std::mem::take(&mut self.table);
+            self.write_text();
+            self.lines.extend(table_lines(&table));
+        }
+    }
+}
+
+/// Reads the marker of a list item from the start of the trimmed line, along
+/// with whether it begins an ordered list.
+fn list_marker(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim_start_matches(' ');
+    let followed = |idx: usize| matches!(line[idx..].chars().next(), None|Some(' '));
+
+    if line.starts_with('-') && followed(1) {
+        return Some(("-", false));
+    }
+
+    let digits = line.find(|c: char| !c.is_ascii_digit()).unwrap_or(line.len());
+    let mut idx = digits;
+    if line[idx..].starts_with(['a', 'A', 'i', 'I']) {
+        idx += 1;
+    }
+
+    if digits == 0 {
+        return None;
+    }
+
+    if line[idx..].starts_with('r') {
+        idx += 1;
+    }
+
+    if !line[idx..].starts_with('.') || !followed(idx + 1) {
+        return None;
+    }
+
+    Some((&line[..idx + 1], true))
+}
+
+/// Formats a line of a paragraph, which the parser reads with the escapes of
+/// blocks at the start of it removed.
+fn paragraph_line(line: &str, first: bool) -> String {
+    let redundant = match line.strip_prefix('\\') {
+        // no block may begin within a paragraph
+        Some(rest) if !first => rest.starts_with(|c: char| "#->|".contains(c) || c.is_ascii_digit()),
+        Some(rest) if rest.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => {
+            list_marker(rest).is_none()
+        },
+        _ => false,
+    };
+
+    if redundant {
+        inline(&line[1..])
+    } else {
+        inline(line)
+    }
+}
+
+/// Formats inline text, trimming the end of it while keeping the two spaces
+/// of a line break.
+fn inline(text: &str) -> String {
+    let trimmed = text.trim_end();
+    let mut formatted = escapes(trimmed);
+    if !trimmed.is_empty() && text.ends_with("  ") {
+        formatted.push_str("  ");
+    }
+    formatted
+}
+
+/// Removes the escapes of characters which only have meaning when doubled,
+/// where neither neighbour of the character is the same.
+fn escapes(text: &str) -> String {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut formatted = String::with_capacity(text.len());
+    let mut i = 0;
+
+    // copies the text through the next occurrence of a character
+    macro_rules! verbatim_through {
+        ($end:expr) => {
+            match chars[i..].iter().position(|c| *c == $end) {
+                Some(end) => {
+                    formatted.extend(&chars[i..=i + end]);
+                    i += end + 1;
+                    continue;
+                },
+                None => {},
+            }
+        }
+    }
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                let c = chars[i + 1];
+                let doubled = formatted.ends_with(c) || chars.get(i + 2) == Some(&c);
+                if !matches!(c, '*'|'+'|'='|'~') || doubled {
+                    formatted.push('\\');
+                }
+                formatted.push(c);
+                i += 2;
+                continue;
+            },
+            // link destinations and anchors are read without escapes
+            ']' if chars.get(i + 1) == Some(&'(') => verbatim_through!(')'),
+            '<' => verbatim_through!('>'),
+            _ => {},
+        }
+
+        formatted.push(chars[i]);
+        i += 1;
+    }
+
+    formatted
+}
+
+/// Splits a table row into its cells at each pipe that is neither escaped nor
+/// within inline code or a link.
+fn split_cells(line: &str) -> Vec<&str> {
+    let bytes = line.as_bytes();
+    let mut cells = Vec::new();
+    let mut start = 1;
+    let mut code = false;
+    let mut i = 1;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'`' if code => code = false,
+            b'`' => code = code_close(&line[i + 1..]).is_some(),
+            // the parser reads a link through the next bracket and parenthesis
+            b'[' if !code => if let Some(close) = line[i..].find(']') {
+                i += close;
+                if line[i + 1..].starts_with('(') {
+                    if let Some(close) = line[i..].find(')') {
+                        i += close;
+                    }
+                }
+            },
+            b'|' if !code => {
+                cells.push(&line[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+        i += 1;
+    }
+
+    cells.push(&line[start..]);
+    cells
+}
+
+/// Finds the end of a code span opened before the text.
+fn code_close(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '`' if !escaped => return Some(i),
+            '\\' => escaped = !escaped,
+            _ => escaped = false,
+        }
+    }
+
+    None
+}
+
+/// Splits text into the pieces between which a line may be broken, keeping
+/// links, inline code and anchors whole, along with whether each piece leaves
+/// a link or anchor open.
+fn pieces(text: &str) -> Vec<(&str, bool)> {
+    let bytes = text.as_bytes();
+    let mut pieces = Vec::new();
+    let mut open = false;
+    let mut start = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let mut end = i + 1;
+        match bytes[i] {
+            b' ' => {
+                if let Some(start) = start.take() {
+                    pieces.push((&text[start..i], open));
+                    open = false;
+                }
+                i += 1;
+                continue;
+            },
+            b'\\' => end += text[end..].chars().next().map_or(0, char::len_utf8),
+            b'`' => if let Some(close) = code_close(&text[end..]) {
+                end += close + 1;
+            },
+            // the parser looks no further than the line for the end of a link
+            b'[' => match text[i..].find(']') {
+                Some(close) => {
+                    end = i + close + 1;
+                    if text[end..].starts_with('(') {
+                        match text[end..].find(')') {
+                            Some(close) => end += close + 1,
+                            None => open = true,
+                        }
+                    }
+                },
+                None => open = true,
+            },
+            b'<' => match text[i..].find('>') {
+                Some(close) => end = i + close + 1,
+                None => open = true,
+            },
+            _ => {},
+        }
+
+        start.get_or_insert(i);
+        i = end;
+    }
+
+    if let Some(start) = start {
+        pieces.push((&text[start..], open));
+    }
+
+    pieces
+}
+
+/// The flags at the start of a table cell.
+struct Flags {
+    len: usize,
+    colspan: usize,
+    rowspan: usize,
+    /// Whether the cell describes a column rather than holding content.
+    columns: bool,
+}
+
+impl Flags {
+    /// Reads the flags of a cell in the order the parser reads them.
+    fn read(cell: &str, first: bool, columns: bool) -> Self {
+        let bytes = cell.as_bytes();
+        let mut i = 0;
+        let mut flags = Flags { len: 0, colspan: 1, rowspan: 1, columns, };
+
+        let flag = |i: &mut usize, accepted: &[u8]| {
+            if bytes.get(*i).is_some_and(|b| accepted.contains(b)) {
+                *i += 1;
+                true
+            } else {
+                false
+            }
+        };
+
+        if !(first && flag(&mut i, b"+")) && (first || columns) && flag(&mut i, b":") {
+            flags.columns = true;
+        }
+
+        flag(&mut i, b"=-_");
+        flag(&mut i, b"$^_");
+        flag(&mut i, b"tmb_");
+
+        let span = |i: &mut usize| {
+            let mut digits = String::new();
+            while let Some(b) = bytes.get(*i) {
+                if matches!(b, b'_'|b'0') && digits.is_empty() {
+                    *i += 1;
+                } else if b.is_ascii_digit() {
+                    digits.push(*b as char);
+                    *i += 1;
+                } else {
+                    break;
+                }
+            }
+            digits.parse().unwrap_or(1).max(1)
+        };
+
+        if bytes.get(i).is_some_and(|b| matches!(b, b'_'|b'0') || b.is_ascii_digit()) {
+            flags.colspan = span(&mut i);
+        }
+
+        if bytes.get(i) == Some(&b',') {
+            i += 1;
+            flags.rowspan = span(&mut i);
+        }
+
+        flags.len = i;
+        flags
+    }
+}
+
+/// A cell of a table as it will be written.
+struct Cell {
+    flags: String,
+    content: String,
+    column: usize,
+    colspan: usize,
+    /// Whether the cell ends the line with a break.
+    br: bool,
+}
+
+impl Cell {
+    /// Reads the content of a cell, which ends in a break when it is the last
+    /// of its line and the line ends with two spaces.
+    fn content(segment: &str, last: bool) -> (String, bool) {
+        let content = escapes(segment.trim());
+        let br = last && !content.is_empty() && segment.ends_with("  ");
+        (content, br)
+    }
+
+    fn width(&self) -> usize {
+        self.flags.chars().count() + self.content.chars().count() + 1
+    }
+}
+
+enum TableLine {
+    Caption(String),
+    Cells(Vec<Cell>),
+}
+
+/// Formats the lines of a table so the cells of each column line up, with the
+/// flags of the cells padded to the same width and separated from the
+/// content by a space.
+fn table_lines(lines: &[&str]) -> Vec<String> {
+    let mut table = Vec::new();
+    let mut covered: Vec<usize> = Vec::new();
+    // the index of the last row, which continuation lines continue
+    let mut row = None;
+
+    for line in lines {
+        // the end of the line is kept for the break of its last cell
+        let line = line.trim_start();
+
+        if let Some(caption) = line.strip_prefix("|#") {
+            table.push(TableLine::Caption(inline(caption.trim_start_matches(' '))));
+            continue;
+        }
+
+        let segments = split_cells(line);
+
+        // continuation segments line up with the cells they continue
+        if let (true, Some(row)) = (line.starts_with("|&"), row) {
+            let TableLine::Cells(cells) = &table[row] else {
+                unreachable!();
+            };
+
+            let mut column = cells.last().map_or(0, |c| c.column + c.colspan);
+            let mut continued = Vec::new();
+            for (i, segment) in segments.iter().enumerate() {
+                let (content, br) = Cell::content(
+                    segment.strip_prefix('&').unwrap_or(segment),
+                    i + 1 == segments.len(),
+                );
+                let (column, colspan) = match cells.get(i) {
+                    Some(cell) => (cell.column, cell.colspan),
+                    None => {
+                        column += 1;
+                        (column - 1, 1)
+                    },
+                };
+
+                continued.push(Cell {
+                    flags: "&".to_owned(),
+                    content,
+                    column,
+                    colspan,
+                    br,
+                });
+            }
+
+            table.push(TableLine::Cells(continued));
+            continue;
+        }
+
+        let mut cells = Vec::new();
+        let mut columns = false;
+        let mut column = 0;
+        for (i, segment) in segments.iter().enumerate() {
+            let flags = Flags::read(segment, i == 0, columns);
+            columns = flags.columns;
+
+            // the cells describing columns are not part of the grid
+            if !columns {
+                while covered.get(column).is_some_and(|c| *c > 0) {
+                    column += 1;
+                }
+
+                if covered.len() < column + flags.colspan {
+                    covered.resize(column + flags.colspan, 0);
+                }
+                for c in &mut covered[column..column + flags.colspan] {
+                    *c = flags.rowspan;
+                }
+            }
+
+            let (content, br) = Cell::content(&segment[flags.len..], i + 1 == segments.len());
+            cells.push(Cell {
+                flags: segment[..flags.len].to_owned(),
+                content,
+                column,
+                colspan: flags.colspan,
+                br,
+            });
+            column += flags.colspan;
+        }
+
+        if !columns {
+            for c in &mut covered {
+                *c = c.saturating_sub(1);
+            }
+        }
+
+        row = Some(table.len());
+        table.push(TableLine::Cells(cells));
+    }
+
+    let cells = || table.iter()
+        .filter_map(|l| match l {
+            TableLine::Cells(cells) => Some(cells),
+            TableLine::Caption(_) => None,
+        })
+        .flatten();
+
+    let count = cells().map(|c| c.column + c.colspan).max().unwrap_or(0);
+    let mut flag_widths = vec![0; count];
+    for cell in cells() {
+        let width = &mut flag_widths[cell.column];
+        *width = (*width).max(cell.flags.chars().count());
+    }
+
+    // the width of each column, including the space after it
+    let mut widths = vec![0; count];
+    let width = |cell: &Cell| if cell.content.is_empty() {
+        cell.width()
+    } else {
+        flag_widths[cell.column] + cell.content.chars().count() + 3
+    };
+
+    for cell in cells().filter(|c| c.colspan == 1) {
+        widths[cell.column] = widths[cell.column].max(width(cell));
+    }
+
+    // spanning cells widen the last of their columns when they don't fit
+    for cell in cells().filter(|c| c.colspan > 1) {
+        let available = widths[cell.column..cell.column + cell.colspan].iter().sum::<usize>();
+        if width(cell) > available {
+            widths[cell.column + cell.colspan - 1] += width(cell) - available;
+        }
+    }
+
+    let mut formatted = Vec::new();
+    for line in &table {
+        let cells = match line {
+            TableLine::Caption(caption) if caption.is_empty() => {
+                formatted.push("|#".to_owned());
+                continue;
+            },
+            TableLine::Caption(caption) => {
+                formatted.push(format!("|# {caption}"));
+                continue;
+            },
+            TableLine::Cells(cells) => cells,
+        };
+
+        let mut line = String::new();
+        for cell in cells {
+            let start = widths[..cell.column].iter().sum::<usize>();
+            let len = line.chars().count();
+            line.push_str(&" ".repeat(start.saturating_sub(len)));
+            line.push('|');
+            line.push_str(&cell.flags);
+
+            if !cell.content.is_empty() {
+                let padding = flag_widths[cell.column] - cell.flags.chars().count();
+                line.push_str(&" ".repeat(padding + 1));
+                line.push_str(&cell.content);
+            }
+
+            line.push(' ');
+        }
+
+        let mut line = line.trim_end().to_owned();
+        if cells.last().is_some_and(|c| c.br) {
+            line.push_str("  ");
+        }
+        formatted.push(line);
+    }
+
+    formatted
+}
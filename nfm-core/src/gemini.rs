@@ -0,0 +1,271 @@
+// nfm-core::gemini: Renders No-Flavor Markdown as gemtext.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown as gemtext, the `text/gemini` format of the
+//! Gemini protocol. Gemtext has no inline markup, so links are written as
+//! link lines after the block they appear in.
+
+use crate::{
+    text::{ self, ordered_markers, },
+    tree::{ self, Element, Node, },
+};
+
+/// Renders the html written by the parser as gemtext.
+pub fn render(html: &str) -> String {
+    let mut gemtext = blocks(&tree::parse(html)).join("\n\n");
+    if !gemtext.is_empty() {
+        gemtext.push('\n');
+    }
+    gemtext
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into blocks of their own.
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let gemtext = paragraph(&nodes[start..$end]);
+            if !gemtext.is_empty() {
+                blocks.push(gemtext);
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+                if let Some(block) = block(element) {
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+    blocks
+}
+
+fn block(element: &Element) -> Option<String> {
+    let gemtext = match element.name.as_str() {
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6" => {
+            let level = element.name[1..].parse::<usize>().unwrap_or(1).min(3);
+            let mut links = Vec::new();
+            let content = inline(&element.children, &mut links).replace('\n', " ");
+            let mut lines = vec![ format!("{} {}", "#".repeat(level), content.trim()), ];
+            lines.extend(links);
+            lines.join("\n")
+        },
+        "p"|"caption" => paragraph(&element.children),
+        "hr" => "---".to_owned(),
+        "pre" => {
+            let code = element.elements().find(|e| e.name == "code");
+            let lang = code.and_then(|c| c.attribute("lang")).unwrap_or_default();
+            format!("```{lang}\n{}\n```", element.text().trim_end_matches('\n'))
+        },
+        // tables are laid out as they would be in plain text
+        "table" => format!("```\n{}\n```", text::render(&element.to_html()).trim_end()),
+        "blockquote" => {
+            let content = blocks(&element.children).join("\n");
+            let (links, lines) = content.lines()
+                .filter(|l| !l.is_empty())
+                .partition::<Vec<_>, _>(|l| l.starts_with("=>"));
+            lines.iter()
+                .map(|l| format!("> {l}"))
+                .chain(links.iter().map(|l| (*l).to_owned()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        },
+        "ul"|"ol" => list(element),
+        "section" if element.attribute("class") == Some("footnotes") => {
+            footnotes(element)
+        },
+        "colgroup" => return None,
+        _ => blocks(&element.children).join("\n\n"),
+    };
+
+    if gemtext.is_empty() {
+        None
+    } else {
+        Some(gemtext)
+    }
+}
+
+/// Writes inline nodes as lines of text followed by their links. Images on
+/// their own are only written as links.
+fn paragraph(nodes: &[Node]) -> String {
+    let mut links = Vec::new();
+    let content = inline(nodes, &mut links);
+
+    let images = nodes.iter().all(|n| match n {
+        Node::Element(e) => e.name == "img" || e.name == "br",
+        Node::Text(t) => t.trim().is_empty(),
+    });
+    if images {
+        return links.join("\n");
+    }
+    let mut lines = content.split('\n')
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(guard)
+        .collect::<Vec<_>>();
+
+    if !lines.is_empty() {
+        lines.extend(links);
+    }
+    lines.join("\n")
+}
+
+/// Keeps a line of text from being read as another kind of line.
+fn guard(line: &str) -> String {
+    if ["#", ">", "=>", "* ", "```"].iter().any(|p| line.starts_with(p)) {
+        format!(" {line}")
+    } else {
+        line.to_owned()
+    }
+}
+
+/// Writes a link line.
+fn link_line(url: &str, text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() || text == url {
+        format!("=> {url}")
+    } else {
+        format!("=> {url} {text}")
+    }
+}
+
+/// Writes inline nodes as text, gathering their links as link lines. The
+/// line breaks of the source are joined, as gemtext is wrapped by the reader.
+fn inline(nodes: &[Node], links: &mut Vec<String>) -> String {
+    let mut text = String::new();
+
+    for node in nodes {
+        let element = match node {
+            Node::Text(t) => {
+                text.push_str(&t.replace('\n', " "));
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        match element.name.as_str() {
+            "br" => text.push('\n'),
+            "img" => {
+                let alt = element.attribute("alt").unwrap_or_default();
+                text.push_str(alt);
+                if let Some(src) = element.attribute("src") {
+                    links.push(link_line(src, alt));
+                }
+            },
+            "input" => if element.has_attribute("checked") {
+                text.push_str("[x]");
+            } else {
+                text.push_str("[ ]");
+            },
+            "sup" => match element.footnote_reference() {
+                Some(number) => text.push_str(&format!("[{number}]")),
+                None => text.push_str(&inline(&element.children, links)),
+            },
+            "a" => {
+                let content = inline(&element.children, links);
+                match element.attribute("href") {
+                    // links back to a footnote reference are only useful in html
+                    Some(href) if href.starts_with("#fnref-") => {},
+                    Some(href) if href.starts_with('#') => text.push_str(&content),
+                    Some(href) => {
+                        links.push(link_line(href, &content));
+                        text.push_str(if content.is_empty() { href } else { &content });
+                    },
+                    None => text.push_str(&content),
+                }
+            },
+            _ => if element.is_block() {
+                text.push_str(&blocks(&element.children).join("\n"));
+            } else {
+                text.push_str(&inline(&element.children, links));
+            },
+        }
+    }
+
+    text
+}
+
+/// Writes each item as a list line, flattening nested lists and writing the
+/// links of the items after the list.
+fn list(element: &Element) -> String {
+    let items = element.elements()
+        .filter(|e| e.name == "li")
+        .collect::<Vec<_>>();
+
+    let markers = if element.name == "ol" {
+        ordered_markers(element, items.len()).into_iter().map(|m| format!("{m} ")).collect()
+    } else {
+        vec![String::new(); items.len()]
+    };
+
+    let mut lines = Vec::new();
+    let mut links = Vec::new();
+    for (item, marker) in items.iter().zip(markers) {
+        let content = blocks(&item.children).join("\n");
+        let mut text = Vec::new();
+        let before = lines.len();
+        for line in content.lines().filter(|l| !l.is_empty()) {
+            if line.starts_with("=>") {
+                links.push(line.to_owned());
+            } else if line.starts_with("* ") && !text.is_empty() {
+                // the items of nested lists follow the text of their item
+                lines.push(format!("* {marker}{}", text.join(" ")));
+                text.clear();
+                lines.push(line.to_owned());
+            } else if line.starts_with("* ") {
+                lines.push(line.to_owned());
+            } else {
+                text.push(line);
+            }
+        }
+
+        if !text.is_empty() || lines.len() == before {
+            lines.push(format!("* {marker}{}", text.join(" ")));
+        }
+    }
+
+    lines.extend(links);
+    lines.join("\n")
+}
+
+fn footnotes(element: &Element) -> String {
+    let mut lines = Vec::new();
+    for list in element.elements() {
+        for item in list.elements() {
+            let number = item.attribute("id")
+                .and_then(|id| id.strip_prefix("fn-"))
+                .unwrap_or_default();
+
+            let mut links = Vec::new();
+            let content = inline(&item.children, &mut links);
+            let content = content.split_whitespace().collect::<Vec<_>>().join(" ");
+            lines.push(format!("[{number}] {content}"));
+            lines.extend(links);
+        }
+    }
+
+    lines.join("\n")
+}
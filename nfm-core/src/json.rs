@@ -0,0 +1,421 @@
+// nfm-core::json: Renders the structure of No-Flavor Markdown as json.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders the structure of a parsed document as json, so that it can be
+//! read by tools written in other languages. Every node is an object with a
+//! `type`, and blocks are given the `line` of the source they begin on.
+
+use crate::{
+    ParseResult,
+    tree::{ self, Element, Node, },
+};
+
+enum Value {
+    Bool(bool),
+    Number(usize),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn write(&self, json: &mut String, indent: usize) {
+        match self {
+            Value::Bool(b) => json.push_str(if *b { "true" } else { "false" }),
+            Value::Number(n) => json.push_str(&n.to_string()),
+            Value::String(s) => write_string(json, s),
+            Value::Array(values) if values.is_empty() => json.push_str("[]"),
+            Value::Array(values) => {
+                json.push('[');
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    newline(json, indent + 1);
+                    value.write(json, indent + 1);
+                }
+                newline(json, indent);
+                json.push(']');
+            },
+            Value::Object(entries) if entries.is_empty() => json.push_str("{}"),
+            Value::Object(entries) => {
+                json.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    newline(json, indent + 1);
+                    write_string(json, key);
+                    json.push_str(": ");
+                    value.write(json, indent + 1);
+                }
+                newline(json, indent);
+                json.push('}');
+            },
+        }
+    }
+}
+
+fn newline(json: &mut String, indent: usize) {
+    json.push('\n');
+    json.push_str(&"  ".repeat(indent));
+}
+
+fn write_string(json: &mut String, s: &str) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Where the output written for each line begins, in the order it was
+/// written.
+struct Lines<'p>(&'p [(usize, usize)]);
+
+impl Lines<'_> {
+    /// The line the output of an element was written for.
+    fn of(&self, element: &Element) -> Option<usize> {
+        let i = self.0.partition_point(|(o, _)| *o <= element.offset);
+        i.checked_sub(1).and_then(|i| self.0.get(i)).map(|(_, line)| *line)
+    }
+}
+
+/// Renders the parsed document as json, using where the output of each line
+/// begins to give the position of each block.
+pub fn render(result: &ParseResult, positions: &[(usize, usize)]) -> String {
+    let nodes = tree::parse(&result.output);
+    let lines = Lines(positions);
+
+    let mut metadata: Vec<(String, Value)> = Vec::new();
+    for (key, value) in result.metadata.iter() {
+        match metadata.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = Value::String(value.to_owned()),
+            None => metadata.push((key.to_owned(), Value::String(value.to_owned()))),
+        }
+    }
+
+    let diagnostics = result.diagnostics.iter()
+        .map(|d| Value::Object(vec![
+            ("line".to_owned(), Value::Number(d.line)),
+            ("message".to_owned(), Value::String(d.message.clone())),
+        ]))
+        .collect();
+
+    let document = Value::Object(vec![
+        ("metadata".to_owned(), Value::Object(metadata)),
+        ("diagnostics".to_owned(), Value::Array(diagnostics)),
+        ("blocks".to_owned(), Value::Array(blocks(&nodes, &lines))),
+    ]);
+
+    let mut json = String::new();
+    document.write(&mut json, 0);
+    json.push('\n');
+    json
+}
+
+/// Builds the object of a node from its type and the rest of its entries.
+fn object(kind: &str, entries: Vec<(&str, Value)>) -> Value {
+    let mut object = vec![ ("type".to_owned(), Value::String(kind.to_owned())), ];
+    object.extend(entries.into_iter().map(|(k, v)| (k.to_owned(), v)));
+    Value::Object(object)
+}
+
+/// Renders a run of blocks, leaving out the whitespace before and after each
+/// of them.
+fn blocks(nodes: &[Node], lines: &Lines) -> Vec<Value> {
+    let beside_block = |i: usize| {
+        i == 0 || i + 1 == nodes.len() ||
+            nodes[i - 1].is_block() || nodes[i + 1].is_block()
+    };
+
+    nodes.iter()
+        .enumerate()
+        .filter(|(i, n)| !matches!(n, Node::Text(t) if t.trim().is_empty() && beside_block(*i)))
+        .filter_map(|(_, n)| node(n, lines))
+        .collect()
+}
+
+/// Renders a run of inline nodes.
+fn inlines(nodes: &[Node], lines: &Lines) -> Vec<Value> {
+    nodes.iter().filter_map(|n| node(n, lines)).collect()
+}
+
+fn node(node: &Node, lines: &Lines) -> Option<Value> {
+    let element = match node {
+        Node::Text(t) => return Some(object("text", vec![ ("text", Value::String(t.clone())), ])),
+        Node::Element(element) => element,
+    };
+
+    let mut entries = Vec::new();
+    if element.is_block() {
+        if let Some(line) = lines.of(element) {
+            entries.push(("line", Value::Number(line)));
+        }
+    }
+
+    let children = |entries: &mut Vec<(&str, Value)>| {
+        let children = if element.children.iter().any(Node::is_block) {
+            blocks(&element.children, lines)
+        } else {
+            inlines(&element.children, lines)
+        };
+        entries.push(("children", Value::Array(children)));
+    };
+
+    let kind = match element.name.as_str() {
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6" => {
+            let level = element.name[1..].parse().unwrap_or(1);
+            entries.push(("level", Value::Number(level)));
+            if let Some(id) = element.attribute("id") {
+                entries.push(("id", Value::String(id.to_owned())));
+            }
+            children(&mut entries);
+            "heading"
+        },
+        "p" => {
+            children(&mut entries);
+            "paragraph"
+        },
+        "hr" => "rule",
+        "pre" => {
+            let code = element.elements().find(|e| e.name == "code");
+            if let Some(lang) = code.and_then(|c| c.attribute("lang")) {
+                entries.push(("lang", Value::String(lang.to_owned())));
+            }
+            entries.push(("text", Value::String(element.text())));
+            "code_block"
+        },
+        "blockquote" => {
+            children(&mut entries);
+            "blockquote"
+        },
+        "ul"|"ol" => {
+            entries.push(("ordered", Value::Bool(element.name == "ol")));
+            if let Some(start) = element.attribute("start").and_then(|s| s.parse().ok()) {
+                entries.push(("start", Value::Number(start)));
+            }
+            if let Some(numbering) = element.attribute("type") {
+                entries.push(("numbering", Value::String(numbering.to_owned())));
+            }
+            if element.has_attribute("reversed") {
+                entries.push(("reversed", Value::Bool(true)));
+            }
+            entries.push(("items", Value::Array(blocks(&element.children, lines))));
+            "list"
+        },
+        "li" => {
+            children(&mut entries);
+            "item"
+        },
+        "table" => {
+            table(element, lines, &mut entries);
+            "table"
+        },
+        "nav" if element.attribute("class") == Some("toc") => {
+            children(&mut entries);
+            "toc"
+        },
+        "section" if element.attribute("class") == Some("footnotes") => {
+            let footnotes = element.elements()
+                .flat_map(Element::elements)
+                .map(|item| footnote(item, lines))
+                .collect();
+            entries.push(("footnotes", Value::Array(footnotes)));
+            "footnotes"
+        },
+        "br" => "break",
+        "strong" => {
+            children(&mut entries);
+            "strong"
+        },
+        "em" => {
+            children(&mut entries);
+            "emphasis"
+        },
+        "del" => {
+            children(&mut entries);
+            "deleted"
+        },
+        "ins" => {
+            children(&mut entries);
+            "inserted"
+        },
+        "mark" => {
+            children(&mut entries);
+            "marked"
+        },
+        "code" => {
+            entries.push(("text", Value::String(element.text())));
+            "code"
+        },
+        "sup" => match element.footnote_reference() {
+            Some(number) => {
+                entries.push(("number", Value::String(number)));
+                "footnote_reference"
+            },
+            None => {
+                children(&mut entries);
+                "superscript"
+            },
+        },
+        "img" => {
+            entries.push(("src", Value::String(element.attribute("src").unwrap_or_default().to_owned())));
+            entries.push(("alt", Value::String(element.attribute("alt").unwrap_or_default().to_owned())));
+            "image"
+        },
+        "input" => {
+            entries.push(("checked", Value::Bool(element.has_attribute("checked"))));
+            "checkbox"
+        },
+        "a" => match (element.attribute("href"), element.attribute("id")) {
+            // links back to a footnote reference are only useful in html
+            (Some(href), _) if href.starts_with("#fnref-") => return None,
+            (Some(href), _) => {
+                entries.push(("href", Value::String(href.to_owned())));
+                children(&mut entries);
+                "link"
+            },
+            (None, Some(id)) => {
+                entries.push(("id", Value::String(id.to_owned())));
+                "anchor"
+            },
+            (None, None) => {
+                children(&mut entries);
+                "link"
+            },
+        },
+        name => {
+            children(&mut entries);
+            name
+        },
+    };
+
+    Some(object(kind, entries))
+}
+
+fn footnote(item: &Element, lines: &Lines) -> Value {
+    let mut entries = Vec::new();
+    if let Some(line) = lines.of(item) {
+        entries.push(("line", Value::Number(line)));
+    }
+
+    let number = item.attribute("id")
+        .and_then(|id| id.strip_prefix("fn-"))
+        .unwrap_or_default();
+    entries.push(("number", Value::String(number.to_owned())));
+
+    // the links back to the references are left out along with the space
+    // before them
+    let mut content = item.children.iter()
+        .filter(|n| !matches!(
+            n,
+            Node::Element(e) if e.attribute("href").is_some_and(|h| h.starts_with("#fnref-")),
+        ))
+        .cloned()
+        .collect::<Vec<_>>();
+    if let Some(Node::Text(text)) = content.last_mut() {
+        text.truncate(text.trim_end().len());
+        if text.is_empty() {
+            content.pop();
+        }
+    }
+
+    let children = inlines(&content, lines);
+    entries.push(("children", Value::Array(children)));
+    object("footnote", entries)
+}
+
+fn table(element: &Element, lines: &Lines, entries: &mut Vec<(&str, Value)>) {
+    let mut columns = Vec::new();
+    let mut rows = Vec::new();
+
+    for child in element.elements() {
+        match child.name.as_str() {
+            "caption" => entries.push(("caption", Value::Array(inlines(&child.children, lines)))),
+            "colgroup" => for col in child.elements() {
+                let mut column = vec![ ("span".to_owned(), Value::Number(col.span("span"))), ];
+                let width = col.attribute("style")
+                    .and_then(|s| s.split(';').find_map(|d| d.trim().strip_prefix("width:")));
+                if let Some(width) = width {
+                    column.push(("width".to_owned(), Value::String(width.trim().to_owned())));
+                }
+                columns.push(Value::Object(column));
+            },
+            "thead"|"tbody"|"tfoot" => {
+                let section = match child.name.as_str() {
+                    "thead" => "head",
+                    "tfoot" => "foot",
+                    _ => "body",
+                };
+                for row in child.elements().filter(|e| e.name == "tr") {
+                    rows.push(table_row(row, section, lines));
+                }
+            },
+            "tr" => rows.push(table_row(child, "body", lines)),
+            _ => {},
+        }
+    }
+
+    if !columns.is_empty() {
+        entries.push(("columns", Value::Array(columns)));
+    }
+    entries.push(("rows", Value::Array(rows)));
+}
+
+fn table_row(row: &Element, section: &str, lines: &Lines) -> Value {
+    let mut entries = Vec::new();
+    if let Some(line) = lines.of(row) {
+        entries.push(("line", Value::Number(line)));
+    }
+    entries.push(("section", Value::String(section.to_owned())));
+
+    let cells = row.elements()
+        .filter(|e| e.name == "td" || e.name == "th")
+        .map(|cell| {
+            let mut entries = vec![
+                ("header", Value::Bool(cell.name == "th")),
+                ("colspan", Value::Number(cell.span("colspan"))),
+                ("rowspan", Value::Number(cell.span("rowspan"))),
+            ];
+            if let Some(scope) = cell.attribute("scope") {
+                entries.push(("scope", Value::String(scope.to_owned())));
+            }
+            let align = cell.alignment().unwrap_or("center");
+            entries.push(("align", Value::String(align.to_owned())));
+            let valign = cell.vertical_alignment().unwrap_or("baseline");
+            entries.push(("valign", Value::String(valign.to_owned())));
+            let children = if cell.children.iter().any(Node::is_block) {
+                blocks(&cell.children, lines)
+            } else {
+                inlines(&cell.children, lines)
+            };
+            entries.push(("children", Value::Array(children)));
+            object("cell", entries)
+        })
+        .collect();
+
+    entries.push(("cells", Value::Array(cells)));
+    object("row", entries)
+}
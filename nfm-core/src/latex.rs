@@ -0,0 +1,442 @@
+// nfm-core::latex: Renders No-Flavor Markdown as LaTeX.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown as the body of a LaTeX document, which is
+//! expected to load the `hyperref`, `listings`, `multirow` and `ulem`
+//! packages.
+
+use crate::{
+    text::ordered_markers,
+    tree::{ self, Element, Node, },
+};
+
+/// The definitions of footnotes by their number, written where they are
+/// referenced.
+type Footnotes<'a> = Vec<(&'a str, &'a Element)>;
+
+/// Renders the html written by the parser as LaTeX.
+pub fn render(html: &str) -> String {
+    let nodes = tree::parse(html);
+
+    let mut footnotes = Vec::new();
+    for section in nodes.iter().filter_map(|n| match n {
+        Node::Element(e) if e.name == "section" && e.attribute("class") == Some("footnotes") => Some(e),
+        _ => None,
+    }) {
+        for item in section.elements().flat_map(Element::elements) {
+            if let Some(number) = item.attribute("id").and_then(|id| id.strip_prefix("fn-")) {
+                footnotes.push((number, item));
+            }
+        }
+    }
+
+    let mut latex = blocks(&nodes, &footnotes).join("\n\n");
+    if !latex.is_empty() {
+        latex.push('\n');
+    }
+    latex
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into blocks of their own.
+fn blocks(nodes: &[Node], footnotes: &Footnotes) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let latex = inline(&nodes[start..$end], footnotes);
+            let latex = latex.trim();
+            if !latex.is_empty() {
+                blocks.push(latex.to_owned());
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+                if let Some(block) = block(element, footnotes) {
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+    blocks
+}
+
+fn block(element: &Element, footnotes: &Footnotes) -> Option<String> {
+    let latex = match element.name.as_str() {
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6" => heading(element, footnotes),
+        "p" => inline(&element.children, footnotes).trim().to_owned(),
+        "hr" => "\\noindent\\rule{\\linewidth}{0.4pt}".to_owned(),
+        "pre" => code_block(element),
+        "blockquote" => environment("quote", &blocks(&element.children, footnotes).join("\n\n")),
+        "ul"|"ol" => list(element, footnotes),
+        "table" => table(element, footnotes),
+        "nav" if element.attribute("class") == Some("toc") => "\\tableofcontents".to_owned(),
+        // footnotes are written where they are referenced
+        "section" if element.attribute("class") == Some("footnotes") => return None,
+        "colgroup" => return None,
+        _ => blocks(&element.children, footnotes).join("\n\n"),
+    };
+
+    if latex.is_empty() {
+        None
+    } else {
+        Some(latex)
+    }
+}
+
+fn environment(name: &str, content: &str) -> String {
+    format!("\\begin{{{name}}}\n{content}\n\\end{{{name}}}")
+}
+
+fn heading(element: &Element, footnotes: &Footnotes) -> String {
+    let command = match element.name.as_str() {
+        "h1" => "section",
+        "h2" => "subsection",
+        "h3" => "subsubsection",
+        "h4" => "paragraph",
+        _ => "subparagraph",
+    };
+
+    // anchors within the heading are labelled after it, once
+    let mut ids = element.attribute("id").into_iter().collect::<Vec<_>>();
+    let mut children = Vec::new();
+    for child in &element.children {
+        match child {
+            Node::Element(e) if e.name == "a" && !e.has_attribute("href") => {
+                if let Some(id) = e.attribute("id").filter(|id| !ids.contains(id)) {
+                    ids.push(id);
+                }
+            },
+            _ => children.push(child.clone()),
+        }
+    }
+
+    let content = inline(&children, footnotes);
+    let mut latex = format!("\\{command}{{{}}}", content.trim().replace('\n', " "));
+    for id in ids {
+        latex.push_str(&label(id));
+    }
+    latex
+}
+
+fn label(id: &str) -> String {
+    format!("\\label{{{}}}", id.replace(['\\', '{', '}', '%', '#'], ""))
+}
+
+fn code_block(element: &Element) -> String {
+    let code = element.elements().find(|e| e.name == "code");
+    let text = element.text();
+    let text = text.trim_end_matches('\n');
+    match code.and_then(|c| c.attribute("lang")) {
+        Some(lang) => format!("\\begin{{lstlisting}}[language={lang}]\n{text}\n\\end{{lstlisting}}"),
+        None => environment("verbatim", text),
+    }
+}
+
+/// Escapes the characters of text which LaTeX would otherwise read as markup.
+fn escape(text: &str) -> String {
+    let mut latex = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => latex.push_str("\\textbackslash{}"),
+            '~' => latex.push_str("\\textasciitilde{}"),
+            '^' => latex.push_str("\\textasciicircum{}"),
+            '{'|'}'|'$'|'&'|'%'|'#'|'_' => {
+                latex.push('\\');
+                latex.push(c);
+            },
+            _ => latex.push(c),
+        }
+    }
+    latex
+}
+
+/// Escapes the characters of a url which would end the argument of `\href`.
+fn url(href: &str) -> String {
+    href.replace('\\', "/").replace('#', "\\#").replace('%', "\\%")
+}
+
+fn inline(nodes: &[Node], footnotes: &Footnotes) -> String {
+    let mut latex = String::new();
+
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::Text(t) => {
+                latex.push_str(&escape(t));
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        let content = || inline(&element.children, footnotes);
+        match element.name.as_str() {
+            // the break is usually followed by the line break of the source
+            "br" => match nodes.get(i + 1) {
+                Some(Node::Text(t)) if t.starts_with('\n') => latex.push_str("\\\\"),
+                _ => latex.push_str("\\\\\n"),
+            },
+            "strong" => latex.push_str(&format!("\\textbf{{{}}}", content())),
+            "em" => latex.push_str(&format!("\\emph{{{}}}", content())),
+            "del" => latex.push_str(&format!("\\sout{{{}}}", content())),
+            "ins" => latex.push_str(&format!("\\underline{{{}}}", content())),
+            "code" => latex.push_str(&format!("\\texttt{{{}}}", escape(&element.text()))),
+            "img" => latex.push_str(&escape(element.attribute("alt").unwrap_or_default())),
+            "input" => if element.has_attribute("checked") {
+                latex.push_str("{[x]}");
+            } else {
+                latex.push_str("{[ ]}");
+            },
+            "sup" => match element.footnote_reference() {
+                Some(number) => latex.push_str(&footnote(&number.to_string(), footnotes)),
+                None => latex.push_str(&format!("\\textsuperscript{{{}}}", content())),
+            },
+            "a" => match (element.attribute("href"), element.attribute("id")) {
+                // links back to a footnote reference are only useful in html
+                (Some(href), _) if href.starts_with("#fnref-") => {},
+                (Some(href), _) if href.starts_with('#') => latex.push_str(&format!(
+                    "\\hyperref[{}]{{{}}}",
+                    href[1..].replace(['\\', '{', '}', '%', '#'], ""),
+                    content(),
+                )),
+                (Some(href), _) if element.text().is_empty() || element.text() == href => {
+                    latex.push_str(&format!("\\url{{{}}}", url(href)));
+                },
+                (Some(href), _) => latex.push_str(&format!("\\href{{{}}}{{{}}}", url(href), content())),
+                (None, Some(id)) => latex.push_str(&label(id)),
+                (None, None) => latex.push_str(&content()),
+            },
+            _ => latex.push_str(&content()),
+        }
+    }
+
+    latex
+}
+
+/// Writes the definition of a footnote where it is referenced. Footnotes
+/// referenced from within footnotes are only numbered.
+fn footnote(number: &str, footnotes: &Footnotes) -> String {
+    match footnotes.iter().find(|(n, _)| *n == number) {
+        Some((_, item)) => {
+            let content = blocks(&item.children, &Vec::new()).join("\n\n");
+            format!("\\footnote{{{}}}", content.trim())
+        },
+        None => format!("\\textsuperscript{{{number}}}"),
+    }
+}
+
+fn list(element: &Element, footnotes: &Footnotes) -> String {
+    let items = element.elements()
+        .filter(|e| e.name == "li")
+        .collect::<Vec<_>>();
+
+    // items are given their markers when LaTeX wouldn't number them the same
+    let numbered = element.attribute("type").unwrap_or("1") == "1" &&
+        element.attribute("start").unwrap_or("1") == "1" &&
+        !element.has_attribute("reversed");
+    let markers = if element.name == "ol" && !numbered {
+        ordered_markers(element, items.len()).into_iter().map(Some).collect()
+    } else {
+        vec![None; items.len()]
+    };
+
+    let mut lines = Vec::new();
+    for (item, marker) in items.iter().zip(markers) {
+        let content = blocks(&item.children, footnotes).join("\n");
+        let mut content = content.lines();
+        let first = content.next().unwrap_or_default();
+
+        let mut line = match marker {
+            Some(marker) => format!("\\item[{marker}]"),
+            None => "\\item".to_owned(),
+        };
+        if !first.is_empty() {
+            // a bracket would otherwise be read as the marker of the item
+            line.push_str(if first.starts_with('[') { " {}" } else { " " });
+            line.push_str(first);
+        }
+        lines.push(line);
+
+        for line in content {
+            if line.is_empty() {
+                lines.push(String::new());
+            } else {
+                lines.push(format!("    {line}"));
+            }
+        }
+    }
+
+    let name = if element.name == "ol" { "enumerate" } else { "itemize" };
+    environment(name, &lines.join("\n"))
+}
+
+struct Cell {
+    content: String,
+    row: usize,
+    column: usize,
+    rowspan: usize,
+    colspan: usize,
+    align: char,
+}
+
+fn align(cell: &Element) -> char {
+    match cell.alignment() {
+        Some("left") => 'l',
+        Some("right") => 'r',
+        _ => 'c',
+    }
+}
+
+/// Writes a table as a `tabular`, placed in a `table` when it has a caption.
+fn table(element: &Element, footnotes: &Footnotes) -> String {
+    let mut caption = None;
+    // each row, and whether a rule is drawn beneath it
+    let mut rows = Vec::new();
+
+    for child in element.elements() {
+        match child.name.as_str() {
+            "caption" => caption = Some(inline(&child.children, footnotes).trim().to_owned()),
+            "thead"|"tbody"|"tfoot" => {
+                if child.name == "tfoot" {
+                    if let Some((_, rule)) = rows.last_mut() {
+                        *rule = true;
+                    }
+                }
+                rows.extend(child.elements().filter(|e| e.name == "tr").map(|r| (r, false)));
+                if child.name == "thead" {
+                    if let Some((_, rule)) = rows.last_mut() {
+                        *rule = true;
+                    }
+                }
+            },
+            "tr" => rows.push((child, false)),
+            _ => {},
+        }
+    }
+
+    // the cell covering each column of each row
+    let mut grid: Vec<Vec<Option<usize>>> = vec![Vec::new(); rows.len()];
+    let mut cells = Vec::new();
+
+    for (r, (row, _)) in rows.iter().enumerate() {
+        let mut column = 0;
+        for cell in row.elements().filter(|e| e.name == "td" || e.name == "th") {
+            while grid[r].get(column).is_some_and(Option::is_some) {
+                column += 1;
+            }
+
+            let colspan = cell.span("colspan");
+            let rowspan = cell.span("rowspan").min(rows.len() - r);
+            for covered in &mut grid[r..r + rowspan] {
+                if covered.len() < column + colspan {
+                    covered.resize(column + colspan, None);
+                }
+                for c in &mut covered[column..column + colspan] {
+                    *c = Some(cells.len());
+                }
+            }
+
+            // breaks can't be written within a cell of a fixed column
+            let content = blocks(&cell.children, footnotes).join(" ")
+                .replace("\\\\\n", " ")
+                .replace("\\\\", "")
+                .replace('\n', " ");
+            let content = if cell.name == "th" && !content.is_empty() {
+                format!("\\textbf{{{content}}}")
+            } else {
+                content
+            };
+
+            cells.push(Cell { content, row: r, column, rowspan, colspan, align: align(cell), });
+            column += colspan;
+        }
+    }
+
+    // columns are aligned as most of their cells are
+    let columns = grid.iter().map(Vec::len).max().unwrap_or(0);
+    let spec = (0..columns).map(|column| {
+        let aligns = cells.iter()
+            .filter(|c| c.column == column && c.colspan == 1)
+            .map(|c| c.align)
+            .collect::<Vec<_>>();
+        let count = |a: &char| aligns.iter().filter(|b| *b == a).count();
+        let mut most = 'l';
+        for a in &aligns {
+            if count(a) > count(&most) {
+                most = *a;
+            }
+        }
+        most
+    }).collect::<Vec<_>>();
+
+    let mut lines = vec![
+        format!("\\begin{{tabular}}{{{}}}", spec.iter().collect::<String>()),
+        "\\hline".to_owned(),
+    ];
+
+    for (r, (_, rule)) in rows.iter().enumerate() {
+        let mut entries = Vec::new();
+        let mut column = 0;
+        while column < columns {
+            let Some(cell) = grid[r].get(column).copied().flatten().map(|c| &cells[c]) else {
+                entries.push(String::new());
+                column += 1;
+                continue;
+            };
+
+            // rows a cell spans into are left empty beneath it
+            let mut entry = if cell.row == r {
+                cell.content.clone()
+            } else {
+                String::new()
+            };
+            if cell.row == r && cell.rowspan > 1 {
+                entry = format!("\\multirow{{{}}}{{*}}{{{entry}}}", cell.rowspan);
+            }
+            if cell.colspan > 1 || cell.align != spec[column] {
+                entry = format!("\\multicolumn{{{}}}{{{}}}{{{entry}}}", cell.colspan, cell.align);
+            }
+
+            entries.push(entry);
+            column += cell.colspan;
+        }
+
+        lines.push(format!("{} \\\\", entries.join(" & ")).trim_start().to_owned());
+        if *rule {
+            lines.push("\\hline".to_owned());
+        }
+    }
+
+    if lines.last().is_some_and(|l| l != "\\hline") {
+        lines.push("\\hline".to_owned());
+    }
+    lines.push("\\end{tabular}".to_owned());
+
+    let tabular = lines.join("\n");
+    match caption {
+        Some(caption) => format!(
+            "\\begin{{table}}[h]\n\\centering\n\\caption{{{caption}}}\n{tabular}\n\\end{{table}}",
+        ),
+        None => tabular,
+    }
+}
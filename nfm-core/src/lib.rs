@@ -18,8 +18,24 @@
 
 #[cfg(test)]
 mod test;
+mod ansi;
+mod commonmark;
+pub mod convert;
+pub mod formatter;
+mod gemini;
+#[cfg(feature = "json")]
+mod json;
+mod latex;
+mod man;
+mod text;
+pub mod tree;
 
 use std::{
+    fmt::{
+        Display,
+        Formatter,
+        Result as FmtResult,
+    },
     fs::OpenOptions,
     io::{
         Read,
@@ -29,15 +45,548 @@ use std::{
     str::Lines,
 };
 
+/// Options controlling how a document is parsed.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Only treat `#` through `######` as a heading when followed by
+    /// whitespace or the end of the line.
+    pub strict_headings: bool,
+    /// Fill the columns a table row leaves uncovered with empty cells.
+    pub pad_tables: bool,
+    /// How the alignment and spans of table cells are written.
+    pub table_alignment: TableAlignment,
+    /// Give every heading an id generated from its text, unless it begins
+    /// with an anchor giving it one.
+    pub heading_ids: bool,
+    /// The lowest level of heading included in a table of contents.
+    pub toc_min_level: usize,
+    /// The highest level of heading included in a table of contents.
+    pub toc_max_level: usize,
+    /// Wrap the output in a standalone html document.
+    pub document: Option<Document>,
+    /// Place the output within a template instead, as with
+    /// [`ParseResult::template`]. A template is used in place of a document
+    /// when both are given.
+    pub template: Option<String>,
+    /// The format the output is written in. Documents and templates only
+    /// apply to html.
+    pub format: Format,
+    /// The column at which output for a terminal is wrapped.
+    pub width: usize,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strict_headings: false,
+            pad_tables: false,
+            table_alignment: TableAlignment::default(),
+            heading_ids: false,
+            toc_min_level: 1,
+            toc_max_level: 6,
+            document: None,
+            template: None,
+            format: Format::default(),
+            width: 80,
+        }
+    }
+}
+
+/// How the alignment of table cells is written.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TableAlignment {
+    /// The `align` and `valign` attributes, alongside every span.
+    #[default]
+    Attributes,
+    /// A `style` attribute setting `text-align` and `vertical-align`, for
+    /// only the alignments given by the cell or its column. Spans of a single
+    /// cell are left out.
+    Style,
+    /// Classes made up of the given prefix followed by `align-` or `valign-`
+    /// and the alignment, such as `nfm-align-left`, for only the alignments
+    /// given by the cell or its column. Spans of a single cell are left out.
+    Classes(String),
+}
+
+/// The format a document is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Html,
+    /// Plain text, with the markers of inline elements removed.
+    Text,
+    /// CommonMark with the GitHub extensions, falling back to html for
+    /// anything else.
+    CommonMark,
+    /// Text styled with ansi escape sequences for a terminal, wrapped at
+    /// [`Options::width`].
+    Ansi,
+    /// A roff man page, using the macros of man(7).
+    Man,
+    /// The body of a LaTeX document.
+    Latex,
+    /// Gemtext, the format of the Gemini protocol.
+    Gemini,
+    /// The structure of the document as json, along with its metadata and
+    /// diagnostics.
+    #[cfg(feature = "json")]
+    Json,
+}
+
+/// The parts of a standalone html document which surround the output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    /// The language of the document, used in place of a `lang` metadata
+    /// entry or `en`.
+    pub lang: Option<String>,
+    /// The title of the document, used in place of a `title` metadata entry
+    /// or the first level one heading.
+    pub title: Option<String>,
+    /// Urls of stylesheets to link to.
+    pub stylesheets: Vec<String>,
+    /// Css written into a `style` element.
+    pub css: Option<String>,
+}
+
+/// A problem found in the source of a document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The line the problem was found on, starting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The key and value pairs of a metadata block at the top of a document.
+///
+/// ```markdown
+/// ---
+/// title: No-Flavor Markdown
+/// tags: markdown, html
+/// ---
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metadata {
+    entries: Vec<(String, String)>,
+}
+
+impl Metadata {
+    /// The value of the last entry with the key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries.iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The value of the last entry with the key, split on commas.
+    pub fn list(&self, key: &str) -> Vec<&str> {
+        match self.get(key) {
+            Some(value) => value.split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Every entry in the order they were written.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Display for Metadata {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for (key, value) in self.iter() {
+            writeln!(f, "{key}: {value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A heading of a parsed document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    pub level: usize,
+    /// The id of the heading, if it was given one.
+    pub id: Option<String>,
+    /// The content of the heading as html.
+    pub html: String,
+    /// The content of the heading as text.
+    pub text: String,
+}
+
+/// A heading within the outline of a document, along with the headings
+/// beneath it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    pub heading: Heading,
+    pub children: Vec<OutlineEntry>,
+}
+
+fn outline_entries(headings: &[&Heading]) -> Vec<OutlineEntry> {
+    let mut entries = Vec::new();
+    let mut i = 0;
+
+    while i < headings.len() {
+        let level = headings[i].level;
+        let end = headings[i + 1..].iter()
+            .position(|h| h.level <= level)
+            .map_or(headings.len(), |p| i + 1 + p);
+
+        entries.push(OutlineEntry {
+            heading: headings[i].clone(),
+            children: outline_entries(&headings[i + 1..end]),
+        });
+
+        i = end;
+    }
+
+    entries
+}
+
+fn write_outline(output: &mut String, entries: &[OutlineEntry]) {
+    output.push_str("<ul>");
+
+    for entry in entries {
+        output.push_str("<li>");
+
+        let text = encode(&entry.heading.text);
+        match &entry.heading.id {
+            Some(id) => {
+                output.push_str("<a href=\"#");
+                output.push_str(&encode_attribute(id));
+                output.push_str("\">");
+                output.push_str(&text);
+                output.push_str("</a>");
+            },
+            None => output.push_str(&text),
+        }
+
+        if !entry.children.is_empty() {
+            write_outline(output, &entry.children);
+        }
+
+        output.push_str("</li>");
+    }
+
+    output.push_str("</ul>");
+}
+
+/// The output of a parsed document.
+#[derive(Debug, Clone, Default)]
+pub struct ParseResult {
+    pub output: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub headings: Vec<Heading>,
+    pub metadata: Metadata,
+}
+
+impl ParseResult {
+    /// Nests the headings from `min_level` to `max_level` beneath the
+    /// nearest heading of a lower level before them.
+    pub fn outline(&self, min_level: usize, max_level: usize) -> Vec<OutlineEntry> {
+        let headings = self.headings.iter()
+            .filter(|h| h.level >= min_level && h.level <= max_level)
+            .collect::<Vec<_>>();
+
+        outline_entries(&headings)
+    }
+
+    /// Writes the outline from `min_level` to `max_level` as a table of
+    /// contents of nested lists linking to each heading.
+    pub fn toc(&self, min_level: usize, max_level: usize) -> String {
+        let outline = self.outline(min_level, max_level);
+        let mut toc = "<nav class=\"toc\">".to_owned();
+        if !outline.is_empty() {
+            write_outline(&mut toc, &outline);
+        }
+        toc.push_str("</nav>");
+        toc
+    }
+
+    /// The `title` metadata entry, or the text of the first level one
+    /// heading.
+    pub fn title(&self) -> Option<&str> {
+        self.metadata.get("title")
+            .or(self.headings.iter().find(|h| h.level == 1).map(|h| h.text.as_str()))
+    }
+
+    /// Fills the placeholders of a template, such as `{{content}}`, with the
+    /// output, `{{title}}` with the title, `{{toc}}` with a table of contents
+    /// and any other `{{key}}` with the metadata entry of that key.
+    /// Placeholders without a value are removed.
+    pub fn template(&self, template: &str) -> String {
+        self.fill_template(template, 1, 6)
+    }
+
+    fn fill_template(&self, template: &str, toc_min_level: usize, toc_max_level: usize) -> String {
+        let mut html = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+
+            html.push_str(&rest[..start]);
+            match rest[start + 2..start + end].trim() {
+                "content" => html.push_str(&self.output),
+                "toc" => html.push_str(&self.toc(toc_min_level, toc_max_level)),
+                // values may be placed within attributes as well as text
+                "title" => html.push_str(&encode_attribute(self.title().unwrap_or_default())),
+                key => html.push_str(&encode_attribute(self.metadata.get(key).unwrap_or_default())),
+            }
+
+            rest = &rest[start + end + 2..];
+        }
+
+        html.push_str(rest);
+        html
+    }
+
+    /// Wraps the output in a standalone html document.
+    pub fn document(&self, document: &Document) -> String {
+        let lang = document.lang.as_deref()
+            .or(self.metadata.get("lang"))
+            .unwrap_or("en");
+        let title = document.title.as_deref()
+            .or(self.title())
+            .unwrap_or_default();
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n");
+        html.push_str(&format!("<html lang=\"{}\">\n", encode_attribute(lang)));
+        html.push_str("<head>\n");
+        html.push_str("<meta charset=\"utf-8\">\n");
+        html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+        html.push_str(&format!("<title>{}</title>\n", encode(title)));
+
+        for stylesheet in &document.stylesheets {
+            html.push_str(&format!(
+                "<link rel=\"stylesheet\" href=\"{}\">\n",
+                encode_attribute(stylesheet),
+            ));
+        }
+
+        if let Some(css) = &document.css {
+            html.push_str("<style>\n");
+            html.push_str(css.trim_end());
+            html.push_str("\n</style>\n");
+        }
+
+        html.push_str("</head>\n");
+        html.push_str("<body>\n");
+        html.push_str(&self.output);
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            html.push('\n');
+        }
+        html.push_str("</body>\n");
+        html.push_str("</html>\n");
+        html
+    }
+}
+
+/// A table cell. Flags which were left out are `None` until the cell is
+/// given the defaults of its column, while `_` sets them to the default of
+/// every cell.
+#[derive(Debug, Default)]
+struct TableCell {
+    /// The scope of a header cell, or `Some(None)` for a plain cell.
+    scope: Option<Option<&'static str>>,
+    align: Option<&'static str>,
+    valign: Option<&'static str>,
+    colspan: usize,
+    rowspan: usize,
+    content: String,
+    /// The closing tag of a list left open in the cell.
+    list: Option<&'static str>,
+}
+
+#[derive(Debug, Default)]
+struct TableRow {
+    /// The line the row begins on.
+    line: usize,
+    cells: Vec<TableCell>,
+    footer: bool,
+    /// The row specifies the defaults of each column rather than content.
+    columns: bool,
+}
+
+/// The layout of a table's cells on a grid of rows and columns.
+#[derive(Debug, Default)]
+struct TableGrid {
+    /// The column each cell begins in.
+    columns: Vec<Vec<usize>>,
+    /// The number of columns each row covers, including those covered by
+    /// cells of earlier rows.
+    covered: Vec<usize>,
+    width: usize,
+    /// The row and index of each cell which covers a column already covered
+    /// by a cell of an earlier row.
+    overlaps: Vec<(usize, usize)>,
+    /// The row and index of each cell which spans beyond the last row.
+    overruns: Vec<(usize, usize)>,
+}
+
+/// A table being collected, which is only written once it ends.
+#[derive(Debug, Default)]
+struct Table {
+    caption: Option<String>,
+    rows: Vec<TableRow>,
+    /// Where the content of the open cell begins in the output.
+    cell_start: Option<usize>,
+    /// The index within the current row of the cell receiving the content.
+    cell_target: usize,
+    /// Whether the content should be separated from the content the cell
+    /// already has by a line break.
+    cell_separate: bool,
+    /// The index of the cell the next segment of a continuation line
+    /// continues.
+    continuing: Option<usize>,
+}
+
+/// Removes the tags from a string of html, decoding the characters the parser
+/// encodes.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {},
+        }
+    }
+
+    tree::decode(&text)
+}
+
+/// Whether the width of a column is a number followed by one of the units
+/// `%`, `px`, `em`, or `ch`.
+fn is_length(width: &str) -> bool {
+    let number = [ "%", "px", "em", "ch", ].iter()
+        .find_map(|unit| width.strip_suffix(unit));
+
+    number.is_some_and(|n| {
+        let (whole, fraction) = n.split_once('.').unwrap_or((n, "0"));
+        !whole.is_empty() && !fraction.is_empty() &&
+            whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+    })
+}
+
+/// Reads a line of a metadata block as a key and a value separated by a
+/// colon, removing quotes around the value.
+fn metadata_entry(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+
+    let value = value.trim();
+    let value = ['"', '\''].into_iter()
+        .find_map(|q| value.strip_prefix(q)?.strip_suffix(q))
+        .unwrap_or(value);
+    Some((key, value))
+}
+
+/// Whether the lines following the opening `---` of a metadata block close it
+/// with another `---`. A blank line or any other text ends the search for the
+/// close.
+pub(crate) fn closes_metadata<'l>(mut lines: impl Iterator<Item = &'l str>) -> bool {
+    lines
+        .find(|l| l.trim_end() == "---" || metadata_entry(l).is_none())
+        .is_some_and(|l| l.trim_end() == "---")
+}
+
+/// Encodes the characters of text which would be mistaken for html.
+fn encode(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Encodes the quotes of a link or an image written within an attribute,
+/// keeping character references as they were written.
+fn encode_quotes(text: &str) -> String {
+    text.replace('"', "&quot;")
+}
+
+/// Encodes text to be written within a quoted attribute.
+fn encode_attribute(text: &str) -> String {
+    text.replace('&', "&amp;").replace('"', "&quot;").replace('\'', "&#39;")
+        .replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Generates an id from text by lowercasing it, joining its words with
+/// hyphens, and removing everything other than letters, numbers, hyphens
+/// and underscores.
+fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        if c.is_alphanumeric() || c == '_' {
+            slug.extend(c.to_lowercase());
+        } else if (c.is_whitespace() || c == '-') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    if slug.is_empty() {
+        slug.push_str("heading");
+    }
+
+    slug
+}
+
+/// A footnote, numbered by the order it was first referenced in.
+#[derive(Debug)]
+struct Footnote {
+    label: String,
+    /// The line the footnote was first referenced on.
+    line: usize,
+    references: usize,
+}
+
+#[derive(Debug)]
+struct FootnoteDefinition {
+    label: String,
+    line: usize,
+    content: String,
+}
+
 #[derive(Debug)]
 pub struct Parser<'a> {
     source: Lines<'a>,
     line: &'a str,
+    line_number: usize,
+    options: Options,
     in_paragraph: bool,
     in_pre_code: bool,
     in_blockquote: bool,
     in_table: bool,
-    table_td_close: Option<String>,
+    table: Table,
     list_nest: Vec<String>,
     in_code_fence: bool,
     code_fence_first: bool,
@@ -49,6 +598,22 @@ pub struct Parser<'a> {
     in_code: bool,
     in_sup: bool,
     output: String,
+    diagnostics: Vec<Diagnostic>,
+    /// The label of the footnote definition being collected, along with
+    /// where its content begins in the output and the line it begins on.
+    footnote_definition: Option<(String, usize, usize)>,
+    footnotes: Vec<Footnote>,
+    footnote_definitions: Vec<FootnoteDefinition>,
+    heading_ids: Vec<String>,
+    headings: Vec<Heading>,
+    metadata: Metadata,
+    /// Where each table of contents belongs in the output, and the line it
+    /// was written on.
+    toc_positions: Vec<(usize, usize)>,
+    /// Where the output written for each line begins, for the positions of
+    /// the json format.
+    #[cfg(feature = "json")]
+    positions: Vec<(usize, usize)>,
 }
 
 impl<'a> Parser<'a> {
@@ -76,6 +641,18 @@ impl<'a> Parser<'a> {
         self.line.is_empty()
     }
 
+    /// The length in bytes of the first character of the line.
+    fn char_len(&self) -> usize {
+        self.line.chars().next().map_or(0, char::len_utf8)
+    }
+
+    /// How an ampersand within code is written. Html keeps it as written,
+    /// while the other formats are rendered from the html and decode its
+    /// character references, so it is escaped for them to show code as is.
+    fn code_ampersand(&self) -> &'static str {
+        if self.options.format == Format::Html { "&" } else { "&amp;" }
+    }
+
     fn split_off(&mut self, n: usize) -> String {
         let s = self.line[0..n].to_owned();
         self.line = &self.line[n..];
@@ -90,23 +667,77 @@ impl<'a> Parser<'a> {
         }
 
         self.line = l.unwrap();
+        self.line_number += 1;
+        self.mark_line(self.line_number);
         true
     }
 
-    fn starts_with_number(&self) -> bool {
-        if self.is_empty() {
-            return false;
+    /// Records that the output from here on was written for a line.
+    #[cfg(feature = "json")]
+    fn mark_line(&mut self, line: usize) {
+        self.positions.push((self.output.len(), line));
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn mark_line(&mut self, _line: usize) {}
+
+    /// Forgets the lines recorded past where output was moved out from.
+    #[cfg(feature = "json")]
+    fn unmark_from(&mut self, start: usize) {
+        while self.positions.last().is_some_and(|(offset, _)| *offset > start) {
+            self.positions.pop();
+        }
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn unmark_from(&mut self, _start: usize) {}
+
+    fn diagnostic(&mut self, message: String) {
+        self.diagnostics.push(Diagnostic { line: self.line_number, message, });
+    }
+
+    /// Reads a metadata block from the top of the document. The block is
+    /// only consumed when every line up to its close is a key and value,
+    /// otherwise it is parsed as usual.
+    fn front_matter(&mut self) {
+        let mut lines = self.source.clone();
+        if lines.next().map(str::trim_end) != Some("---") {
+            return;
+        }
+
+        if !closes_metadata(lines) {
+            return;
+        }
+
+        self.advance_line();
+        while self.advance_line() && self.line.trim_end() != "---" {
+            if let Some((key, value)) = metadata_entry(self.line) {
+                self.metadata.entries.push((key.to_owned(), value.to_owned()));
+            }
         }
+    }
+
+    fn starts_with_number(&self) -> bool {
+        self.line.chars().next().is_some_and(|c| c.is_ascii_digit())
+    }
 
-        matches!(&self.line[0..1], "0"|"1"|"2"|"3"|"4"|"5"|"6"|"7"|"8"|"9")
+    /// Whether the line begins with a backslash escaping a number, which
+    /// would otherwise begin an ordered list.
+    fn escapes_number(&self) -> bool {
+        self.line.strip_prefix('\\')
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
     }
 
     fn starts_with_trimmed_char(&self, c: char) -> bool {
         self.line.trim_start_matches(' ').starts_with(c)
     }
 
-    fn starts_with_trimmed(&self, s: &str) -> bool {
-        self.line.trim_start_matches(' ').starts_with(s)
+    /// Checks that the trimmed line begins with `marker` followed by a space or
+    /// the end of the line.
+    fn starts_with_trimmed_marker(&self, marker: &str) -> bool {
+        let line = self.line.trim_start_matches(' ');
+        line.starts_with(marker) &&
+            matches!(line[marker.len()..].chars().next(), None|Some(' '))
     }
 
     fn trim_start(&mut self) {
@@ -121,6 +752,82 @@ impl<'a> Parser<'a> {
         self.list_nest.len() / 2
     }
 
+    /// Reads an ordered list marker from the start of the trimmed line.
+    ///
+    /// Returns the marker itself along with the opening tag of the list it
+    /// would begin. The marker is made up of a start number (`0` being the
+    /// auto-numbered form), an optional numbering style (`a`, `A`, `i` or
+    /// `I`), an optional `r` for reversed lists and a trailing period followed
+    /// by a space or the end of the line.
+    fn ordered_list_marker(&self) -> Option<(&'a str, String)> {
+        let line = self.line.trim_start_matches(' ');
+
+        let digits = line.find(|c: char| !c.is_ascii_digit()).unwrap_or(line.len());
+        let mut idx = digits;
+
+        let style = match line[idx..].chars().next() {
+            Some(c @ ('a'|'A'|'i'|'I')) => {
+                idx += 1;
+                Some(c)
+            },
+            _ => None,
+        };
+
+        // a style on its own would take in prose such as `I. think`
+        if digits == 0 {
+            return None;
+        }
+
+        let reversed = line[idx..].starts_with('r');
+        if reversed {
+            idx += 1;
+        }
+
+        if !line[idx..].starts_with('.') {
+            return None;
+        }
+
+        idx += 1;
+
+        if idx != line.len() && !line[idx..].starts_with(' ') {
+            return None;
+        }
+
+        let mut open = "<ol".to_owned();
+
+        if let Some(style) = style {
+            open.push_str(" type=\"");
+            open.push(style);
+            open.push('"');
+        }
+
+        // 0 and 1 are both the default start
+        match line[0..digits].parse::<usize>() {
+            Ok(start) if start > 1 => {
+                open.push_str(" start=\"");
+                open.push_str(&start.to_string());
+                open.push('"');
+            },
+            _ => {},
+        }
+
+        if reversed {
+            open.push_str(" reversed=\"reversed\"");
+        }
+
+        open.push('>');
+
+        Some((&line[0..idx], open))
+    }
+
+    /// Reports the start of an ordered list which is too large to be read.
+    fn check_list_start(&mut self, marker: &str) {
+        let digits = marker.find(|c: char| !c.is_ascii_digit()).unwrap_or(marker.len());
+        if marker[..digits].parse::<usize>().is_err() {
+            self.diagnostic(format!("ordered list start `{}` is too large", &marker[..digits]));
+        }
+    }
+
     fn count_indentation_levels(&mut self) -> usize {
         let mut i = 0;
         while self.line.starts_with("    ") {
@@ -195,6 +902,160 @@ impl<'a> Parser<'a> {
         }
     }
 
+    fn count_heading_hashes(&self) -> usize {
+        self.line.len() - self.line.trim_start_matches('#').len()
+    }
+
+    fn heading_hashes_followed_by_whitespace(&self) -> bool {
+        let hashes = self.count_heading_hashes();
+        matches!(self.line[hashes..].chars().next(), None|Some(' ')|Some('\t'))
+    }
+
+    /// Determines the level of the heading beginning the line, if any.
+    fn heading_level(&self) -> Option<usize> {
+        let hashes = self.count_heading_hashes();
+
+        if hashes == 0 {
+            return None;
+        }
+
+        if self.options.strict_headings && (
+            hashes > 6 || !self.heading_hashes_followed_by_whitespace()
+        ) {
+            return None;
+        }
+
+        Some(hashes.min(6))
+    }
+
+    /// Reports a line whose leading hashes may not have been meant as the
+    /// heading they were, or were not, parsed into.
+    fn report_ambiguous_heading(&mut self) {
+        let hashes = self.count_heading_hashes();
+
+        let problem = if hashes == 0 {
+            return;
+        } else if hashes > 6 {
+            "more than six `#`"
+        } else if !self.heading_hashes_followed_by_whitespace() {
+            "`#` not followed by whitespace"
+        } else {
+            return;
+        };
+
+        let outcome = if self.heading_level().is_some() {
+            "parsed as a heading"
+        } else {
+            "parsed as a paragraph"
+        };
+
+        self.diagnostic(format!("ambiguous heading, {problem}; {outcome}"));
+    }
+
+    /// Removes an optional closing sequence of hashes from the end of a
+    /// heading.
+    fn trim_closing_hashes(&mut self) {
+        let line = self.line.trim_end_matches([ ' ', '\t', ]);
+        let content = line.trim_end_matches('#');
+
+        if content.len() == line.len() {
+            return;
+        }
+
+        // the closing sequence must be separated from the content
+        if content.is_empty() || content.ends_with([ ' ', '\t', ]) {
+            self.line = content.trim_end_matches([ ' ', '\t', ]);
+        }
+    }
+
+    /// Reserves the ids given by the anchors beginning headings, so that no id
+    /// generated for an earlier heading takes one of them.
+    fn reserve_heading_ids(&mut self) {
+        for line in self.source.clone() {
+            let rest = line.trim_start_matches('#');
+            if rest.len() == line.len() {
+                continue;
+            }
+
+            let rest = rest.trim_start_matches([ ' ', '\t', ]);
+            if let Some(idx) = rest.strip_prefix('<').and_then(|r| r.find('>')) {
+                let id = rest[1..idx + 1].to_owned();
+                if !self.heading_ids.contains(&id) {
+                    self.heading_ids.push(id);
+                }
+            }
+        }
+    }
+
+    /// Makes an id unique among the ids given to headings by suffixing it
+    /// with a number.
+    fn unique_heading_id(&mut self, id: String) -> String {
+        let mut unique = id.clone();
+        let mut n = 0;
+
+        while self.heading_ids.contains(&unique) {
+            n += 1;
+            unique = format!("{id}-{n}");
+        }
+
+        self.heading_ids.push(unique.clone());
+        unique
+    }
+
+    fn heading(&mut self, level: usize) {
+        self.advance(level);
+        // either may separate the hashes from the content
+        self.line = self.line.trim_start_matches([ ' ', '\t', ]);
+        self.trim_closing_hashes();
+
+        // a leading anchor gives the heading its id
+        let mut id = None;
+        if self.line.starts_with('<') {
+            if let Some(idx) = self.line.find('>') {
+                id = Some(self.line[1..idx].to_owned());
+
+                // the id is moved onto the heading itself, along with the
+                // space separating it from the text
+                if self.options.heading_ids {
+                    self.advance(idx + 1);
+                    self.line = self.line.trim_start_matches([ ' ', '\t', ]);
+                }
+            }
+        }
+
+        let start = self.output.len();
+        self.parse_inline();
+        let content = self.output.split_off(start);
+        let text = strip_tags(&content).trim().to_owned();
+
+        if self.options.heading_ids {
+            let heading_id = match id {
+                Some(id) => {
+                    if !self.heading_ids.contains(&id) {
+                        self.heading_ids.push(id.clone());
+                    }
+                    id
+                },
+                None => self.unique_heading_id(slugify(&text)),
+            };
+
+            self.output.push_str(&format!("<h{level} id=\"{}\">", encode_attribute(&heading_id)));
+            id = Some(heading_id);
+        } else {
+            self.output.push_str(&format!("<h{level}>"));
+        }
+
+        self.output.push_str(&content);
+        self.output.push_str(&format!("</h{level}>\n"));
+
+        self.headings.push(Heading {
+            level,
+            id,
+            html: content,
+            text,
+        });
+    }
+
     fn in_an_element(&mut self) -> bool {
         self.in_paragraph || self.in_pre_code || self.in_blockquote ||
             self.in_table || self.in_code_fence || self.in_list()
@@ -220,6 +1081,30 @@ impl<'a> Parser<'a> {
         true
     }
 
+    /// A backslash followed by the end of the line, or by spaces and the next
+    /// cell, breaks the line within a table cell. A backslash directly before
+    /// a pipe escapes it instead.
+    fn table_cell_br(&mut self) -> bool {
+        if !self.in_table || !self.line.starts_with('\\') {
+            return false;
+        }
+
+        let rest = &self.line[1..];
+        let trimmed = rest.trim_start_matches(' ');
+        let ends_cell = trimmed.is_empty() ||
+            (trimmed.starts_with('|') && trimmed.len() < rest.len());
+        if !ends_cell {
+            return false;
+        }
+
+        self.advance(1);
+        // the spaces before the backslash only separate it from the text
+        let len = self.output.trim_end_matches([ ' ', '\t', ]).len();
+        self.output.truncate(len);
+        self.output.push_str("<br />");
+        true
+    }
+
     fn strong(&mut self) -> bool {
         if self.line.starts_with("**") {
             self.advance(2);
@@ -306,8 +1191,30 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Checks whether an unescaped backtick closes the code span opening at
+    /// the start of the line.
+    fn code_closes_on_line(&self) -> bool {
+        let mut escaped = false;
+        for c in self.line[1..].chars() {
+            match c {
+                '`' if !escaped => return true,
+                '\\' => escaped = !escaped,
+                _ => escaped = false,
+            }
+        }
+
+        false
+    }
+
     fn code(&mut self) -> bool {
         if self.line.starts_with('`') {
+            // within a table a code span must close before the end of the
+            // row, otherwise it would take in the cells that follow
+            if self.in_table && !self.in_code && !self.code_closes_on_line() {
+                self.advance_into_output(1);
+                return true;
+            }
+
             self.advance(1);
             if self.in_code {
                 self.output.push_str("</code>");
@@ -389,6 +1296,145 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Reads the label of a footnote from the start of the line, such as
+    /// `1` from `[^1]`.
+    fn footnote_label(&self) -> Option<&'a str> {
+        if !self.line.starts_with("[^") {
+            return None;
+        }
+
+        let end = self.line.find(']')?;
+        let label = &self.line[2..end];
+
+        if label.is_empty() || label.contains(|c: char| c.is_whitespace() || c == '^') {
+            None
+        } else {
+            Some(label)
+        }
+    }
+
+    fn footnote_reference(&mut self) -> bool {
+        let label = match self.footnote_label() {
+            Some(label) => label,
+            None => return false,
+        };
+
+        // `[^1^](#foot-1)` is a link to a superscript
+        if self.line[label.len() + 3..].starts_with('(') {
+            return false;
+        }
+
+        self.advance(label.len() + 3);
+
+        let number = match self.footnotes.iter().position(|f| f.label == label) {
+            Some(i) => {
+                self.footnotes[i].references += 1;
+                i + 1
+            },
+            None => {
+                self.footnotes.push(Footnote {
+                    label: label.to_owned(),
+                    line: self.line_number,
+                    references: 1,
+                });
+                self.footnotes.len()
+            },
+        };
+
+        let references = self.footnotes[number - 1].references;
+
+        self.output.push_str("<sup><a id=\"fnref-");
+        self.output.push_str(&number.to_string());
+        if references > 1 {
+            self.output.push('-');
+            self.output.push_str(&references.to_string());
+        }
+        self.output.push_str("\" href=\"#fn-");
+        self.output.push_str(&number.to_string());
+        self.output.push_str("\">");
+        self.output.push_str(&number.to_string());
+        self.output.push_str("</a></sup>");
+
+        true
+    }
+
+    /// Writes the definitions of the referenced footnotes, in the order they
+    /// were referenced, to the end of the output.
+    fn write_footnotes(&mut self) {
+        let definitions = std::mem::take(&mut self.footnote_definitions);
+
+        for (i, definition) in definitions.iter().enumerate() {
+            if definitions[0..i].iter().any(|d| d.label == definition.label) {
+                self.diagnostics.push(Diagnostic {
+                    line: definition.line,
+                    message: format!("footnote `{}` is already defined", definition.label),
+                });
+            } else if !self.footnotes.iter().any(|f| f.label == definition.label) {
+                self.diagnostics.push(Diagnostic {
+                    line: definition.line,
+                    message: format!("footnote `{}` is never referenced", definition.label),
+                });
+            }
+        }
+
+        for footnote in &self.footnotes {
+            if !definitions.iter().any(|d| d.label == footnote.label) {
+                self.diagnostics.push(Diagnostic {
+                    line: footnote.line,
+                    message: format!("footnote `{}` is never defined", footnote.label),
+                });
+            }
+        }
+
+        let first = self.footnotes.iter()
+            .find_map(|f| definitions.iter().find(|d| d.label == f.label));
+        match first {
+            Some(definition) => self.mark_line(definition.line),
+            // the section is left out when none of its footnotes are defined
+            None => return,
+        }
+        self.output.push_str("<section class=\"footnotes\"><ol>");
+
+        // whether an undefined footnote was left out, after which the items
+        // must be numbered explicitly to match their references
+        let mut skipped = false;
+        let footnotes = std::mem::take(&mut self.footnotes);
+        for (i, footnote) in footnotes.iter().enumerate() {
+            let number = (i + 1).to_string();
+            let definition = match definitions.iter().find(|d| d.label == footnote.label) {
+                Some(definition) => definition,
+                None => {
+                    skipped = true;
+                    continue;
+                },
+            };
+
+            self.mark_line(definition.line);
+            self.output.push_str("<li id=\"fn-");
+            self.output.push_str(&number);
+            if skipped {
+                self.output.push_str("\" value=\"");
+                self.output.push_str(&number);
+            }
+            self.output.push_str("\">");
+            self.output.push_str(&definition.content);
+
+            for reference in 1..=footnote.references {
+                self.output.push_str(" <a href=\"#fnref-");
+                self.output.push_str(&number);
+                if reference > 1 {
+                    self.output.push('-');
+                    self.output.push_str(&reference.to_string());
+                }
+                self.output.push_str("\">&#8617;</a>");
+            }
+
+            self.output.push_str("</li>");
+        }
+
+        self.output.push_str("</ol></section>\n");
+    }
+
     fn link(&mut self) -> bool {
         if self.line.starts_with('[') {
             let end_idx = match self.line.find(']') {
@@ -409,7 +1455,11 @@ impl<'a> Parser<'a> {
             self.advance(end_idx);
             std::mem::swap(&mut self.line, &mut line);
 
+            // pipes within the text of a link don't separate table cells,
+            // whose content is found by its place in the swapped out output
+            let in_table = std::mem::replace(&mut self.in_table, false);
             self.parse_inline();
+            self.in_table = in_table;
 
             std::mem::swap(&mut self.output, &mut inner);
             std::mem::swap(&mut self.line, &mut line);
@@ -438,7 +1488,7 @@ impl<'a> Parser<'a> {
                 self.advance(1);
 
                 self.output.push_str("<a href=\"");
-                self.output.push_str(&href);
+                self.output.push_str(&encode_quotes(&href));
                 self.output.push_str("\">");
                 self.output.push_str(&inner);
                 self.output.push_str("</a>");
@@ -488,9 +1538,9 @@ impl<'a> Parser<'a> {
             let src = self.split_off(idx);
             self.advance(1);
             self.output.push_str("<img alt=\"");
-            self.output.push_str(&alt_text);
+            self.output.push_str(&encode_quotes(&alt_text));
             self.output.push_str("\" src=\"");
-            self.output.push_str(&src);
+            self.output.push_str(&encode_quotes(&src));
             self.output.push_str("\" />");
 
             true
@@ -499,75 +1549,169 @@ impl<'a> Parser<'a> {
         }
     }
 
+    /// Moves the content of the open table cell out of the output.
+    fn close_table_cell(&mut self) {
+        if let Some(start) = self.table.cell_start.take() {
+            let content = self.output.split_off(start);
+            self.unmark_from(start);
+            let target = self.table.cell_target;
+            let separate = self.table.cell_separate;
+            if let Some(cell) = self.table.rows.last_mut()
+                .and_then(|row| row.cells.get_mut(target))
+            {
+                if separate && !content.is_empty() && !cell.content.is_empty() {
+                    cell.content.push('\n');
+                }
+                cell.content.push_str(&content);
+            }
+        }
+    }
+
+    /// Begins the next segment of a continuation line, which continues the
+    /// cell of the current row in the same position.
+    fn continue_table_cell(&mut self, index: usize) {
+        // the ampersand is optional after the first segment
+        if self.line.starts_with('&') {
+            self.advance(1);
+        }
+
+        self.table.continuing = Some(index + 1);
+        self.trim_start();
+
+        let cells = self.table.rows.last().map_or(0, |row| row.cells.len());
+        if index >= cells {
+            self.diagnostic(format!(
+                "table continuation segment {} has no cell to continue",
+                index + 1,
+            ));
+        }
+
+        // list items may begin within a continued cell
+        let list = if self.starts_with_trimmed_marker("-") {
+            Some(("-", "<ul>".to_owned(), "</ul>"))
+        } else {
+            self.ordered_list_marker()
+                .map(|(marker, open)| (marker, open, "</ol>"))
+        };
+
+        self.table.cell_target = index;
+        self.table.cell_separate = list.is_none();
+
+        if let Some((marker, open, close)) = list {
+            if close == "</ol>" {
+                self.check_list_start(marker);
+            }
+            self.advance(marker.len());
+            self.trim_start();
+
+            if let Some(cell) = self.table.rows.last_mut()
+                .and_then(|row| row.cells.get_mut(index))
+            {
+                match cell.list {
+                    Some(c) if c == close => cell.content.push_str("</li><li>"),
+                    other => {
+                        if let Some(c) = other {
+                            cell.content.push_str("</li>");
+                            cell.content.push_str(c);
+                        }
+                        cell.content.push_str(&open);
+                        cell.content.push_str("<li>");
+                        cell.list = Some(close);
+                    },
+                }
+            }
+        }
+
+        self.table.cell_start = Some(self.output.len());
+    }
+
     fn table(&mut self) -> bool {
         if self.line.starts_with('|') && self.in_table {
             self.advance(1);
 
-            match &self.table_td_close {
-                Some(close) => {
-                    self.output.push_str(close);
-                    self.table_td_close = None;
-                },
-                None => {},
+            self.close_table_cell();
+
+            if let Some(index) = self.table.continuing {
+                self.continue_table_cell(index);
+                return true;
+            }
+
+            // FOOTER/COLUMNS
+            // only the first cell of a row may mark the kind of row
+            let row_is_empty = self.table.rows.last()
+                .is_none_or(|row| row.cells.is_empty());
+            let row_is_columns = self.table.rows.last()
+                .is_some_and(|row| row.columns);
+            if row_is_empty && self.line.starts_with('+') {
+                self.advance(1);
+                if let Some(row) = self.table.rows.last_mut() {
+                    row.footer = true;
+                }
+            }
+            else if (row_is_empty || row_is_columns) && self.line.starts_with(':') {
+                self.advance(1);
+                if let Some(row) = self.table.rows.last_mut() {
+                    row.columns = true;
+                }
             }
 
             // ELEMENT/SCOPE
-            let mut element = "td";
-            let mut scope = "";
+            let mut scope = None;
             // column header
             if self.line.starts_with('=') {
                 self.advance(1);
-                element = "th";
-                scope = "col";
+                scope = Some(Some("col"));
             }
             // row header
             else if self.line.starts_with('-') {
                 self.advance(1);
-                element = "th";
-                scope = "row";
+                scope = Some(Some("row"));
             }
-            // standard td (default. used only for alignment of flags)
+            // standard td (default, or a reset of the column's type)
             else if self.line.starts_with('_') {
                 self.advance(1);
+                scope = Some(None);
             }
 
             // ALIGN
-            let mut align = "center";
+            let mut align = None;
             // right alignment
             if self.line.starts_with('$') {
                 self.advance(1);
-                align = "right";
+                align = Some("right");
             }
             // left alignment
             else if self.line.starts_with('^') {
                 self.advance(1);
-                align = "left";
+                align = Some("left");
             }
-            // center alignment (default. used only for alignment of flags)
+            // center alignment (default, or a reset of the column's alignment)
             else if self.line.starts_with('_') {
                 self.advance(1);
+                align = Some("center");
             }
 
             // VALIGN
-            let mut valign = "baseline";
+            let mut valign = None;
             // top valignment
             if self.line.starts_with('t') {
                 self.advance(1);
-                valign = "top";
+                valign = Some("top");
             }
             // middle valignment
             else if self.line.starts_with('m') {
                 self.advance(1);
-                valign = "middle";
+                valign = Some("middle");
             }
             // bottom valignment
             else if self.line.starts_with('b') {
                 self.advance(1);
-                valign = "bottom";
+                valign = Some("bottom");
             }
-            // baseline valignment (default. used only for alignment of flags)
+            // baseline valignment (default, or a reset of the column's valignment)
             else if self.line.starts_with('_') {
                 self.advance(1);
+                valign = Some("baseline");
             }
 
             // COLSPAN
@@ -632,38 +1776,348 @@ impl<'a> Parser<'a> {
 
             self.trim_start();
 
-            if element == "th" {
-                self.output.push_str("<th scope=\"");
-                self.output.push_str(scope);
-                self.output.push_str("\" align=\"");
-                self.output.push_str(align);
-                self.output.push_str("\" valign=\"");
-                self.output.push_str(valign);
-                self.output.push_str("\" colspan=\"");
-                self.output.push_str(&colspan);
-                self.output.push_str("\" rowspan=\"");
-                self.output.push_str(&rowspan);
-                self.output.push_str("\">");
-                self.table_td_close = Some("</th>".to_owned());
-            } else {
-                self.output.push_str("<td align=\"");
-                self.output.push_str(align);
-                self.output.push_str("\" valign=\"");
-                self.output.push_str(valign);
-                self.output.push_str("\" colspan=\"");
-                self.output.push_str(&colspan);
-                self.output.push_str("\" rowspan=\"");
-                self.output.push_str(&rowspan);
-                self.output.push_str("\">");
-                self.table_td_close = Some("</td>".to_owned());
+            let cell = TableCell {
+                scope,
+                align,
+                valign,
+                colspan: colspan.parse().unwrap_or(1),
+                rowspan: rowspan.parse().unwrap_or(1),
+                content: String::new(),
+                list: None,
+            };
+
+            if self.table.rows.is_empty() {
+                self.table.rows.push(TableRow::default());
             }
 
+            let row = self.table.rows.last_mut().unwrap();
+            row.cells.push(cell);
+            self.table.cell_target = row.cells.len() - 1;
+            self.table.cell_separate = false;
+            self.table.cell_start = Some(self.output.len());
+
             true
         } else {
             false
         }
     }
 
+    fn write_table_row(output: &mut String, row: &TableRow, alignment: &TableAlignment) {
+        output.push_str("<tr>");
+
+        for cell in &row.cells {
+            match cell.scope.flatten() {
+                Some(scope) => {
+                    output.push_str("<th scope=\"");
+                    output.push_str(scope);
+                    output.push('"');
+                },
+                None => output.push_str("<td"),
+            }
+
+            match alignment {
+                TableAlignment::Attributes => {
+                    output.push_str(" align=\"");
+                    output.push_str(cell.align.unwrap_or("center"));
+                    output.push_str("\" valign=\"");
+                    output.push_str(cell.valign.unwrap_or("baseline"));
+                    output.push_str("\" colspan=\"");
+                    output.push_str(&cell.colspan.to_string());
+                    output.push_str("\" rowspan=\"");
+                    output.push_str(&cell.rowspan.to_string());
+                    output.push('"');
+                },
+                // only the alignments which were given are written, leaving
+                // the rest to the stylesheet
+                TableAlignment::Style => {
+                    let style = [
+                        cell.align.map(|a| format!("text-align:{a}")),
+                        cell.valign.map(|v| format!("vertical-align:{v}")),
+                    ];
+                    let style = style.into_iter().flatten().collect::<Vec<_>>();
+                    if !style.is_empty() {
+                        output.push_str(" style=\"");
+                        output.push_str(&style.join(";"));
+                        output.push('"');
+                    }
+                },
+                TableAlignment::Classes(prefix) => {
+                    let classes = [
+                        cell.align.map(|a| format!("{prefix}align-{a}")),
+                        cell.valign.map(|v| format!("{prefix}valign-{v}")),
+                    ];
+                    let classes = classes.into_iter().flatten().collect::<Vec<_>>();
+                    if !classes.is_empty() {
+                        output.push_str(" class=\"");
+                        output.push_str(&classes.join(" "));
+                        output.push('"');
+                    }
+                },
+            }
+
+            if *alignment != TableAlignment::Attributes {
+                if cell.colspan > 1 {
+                    output.push_str(" colspan=\"");
+                    output.push_str(&cell.colspan.to_string());
+                    output.push('"');
+                }
+                if cell.rowspan > 1 {
+                    output.push_str(" rowspan=\"");
+                    output.push_str(&cell.rowspan.to_string());
+                    output.push('"');
+                }
+            }
+
+            output.push('>');
+            output.push_str(&cell.content);
+            output.push_str(match cell.scope.flatten() {
+                Some(_) => "</th>",
+                None => "</td>",
+            });
+        }
+
+        output.push_str("</tr>");
+    }
+
+    /// Lays the cells of the table out on a grid, accounting for the cells
+    /// of earlier rows which span into later ones.
+    fn table_grid(rows: &[TableRow]) -> TableGrid {
+        let mut grid = TableGrid::default();
+        // the number of rows, including the current, each column is covered for
+        let mut covered: Vec<usize> = Vec::new();
+
+        for (r, row) in rows.iter().enumerate() {
+            let mut column = 0;
+            let mut row_columns = Vec::with_capacity(row.cells.len());
+
+            for (c, cell) in row.cells.iter().enumerate() {
+                while covered.get(column).is_some_and(|n| *n > 0) {
+                    column += 1;
+                }
+
+                row_columns.push(column);
+
+                let end = column + cell.colspan.max(1);
+                if covered.len() < end {
+                    covered.resize(end, 0);
+                }
+
+                if covered[column..end].iter().any(|n| *n > 0) {
+                    grid.overlaps.push((r, c));
+                }
+
+                if r + cell.rowspan.max(1) > rows.len() {
+                    grid.overruns.push((r, c));
+                }
+
+                for n in &mut covered[column..end] {
+                    *n = (*n).max(cell.rowspan.max(1));
+                }
+
+                column = end;
+            }
+
+            grid.covered.push(covered.iter().filter(|n| **n > 0).count());
+            grid.width = grid.width.max(covered.len());
+
+            for n in &mut covered {
+                *n = n.saturating_sub(1);
+            }
+
+            grid.columns.push(row_columns);
+        }
+
+        grid
+    }
+
+    /// Writes the collected table to the output.
+    fn write_table(&mut self) {
+        self.close_table_cell();
+        let mut table = std::mem::take(&mut self.table);
+
+        for cell in table.rows.iter_mut().flat_map(|row| row.cells.iter_mut()) {
+            if let Some(close) = cell.list.take() {
+                cell.content.push_str("</li>");
+                cell.content.push_str(close);
+            }
+        }
+
+        // the last column specification applies to the whole table
+        let (column_line, column_specs) = table.rows.iter()
+            .rposition(|row| row.columns)
+            .map(|i| table.rows.remove(i))
+            .map(|row| (row.line, row.cells))
+            .unwrap_or_default();
+        table.rows.retain(|row| !row.columns);
+
+        // the content of a column specification is its width
+        let column_widths = column_specs.iter()
+            .map(|spec| spec.content.trim())
+            .collect::<Vec<_>>();
+        for (c, width) in column_widths.iter().enumerate() {
+            if !width.is_empty() && !is_length(width) {
+                self.diagnostics.push(Diagnostic {
+                    line: column_line,
+                    message: format!(
+                        "column {} width `{}` is not a length",
+                        c + 1,
+                        strip_tags(width),
+                    ),
+                });
+            }
+        }
+
+        let mut column_defaults = Vec::new();
+        for spec in &column_specs {
+            for _ in 0..spec.colspan.max(1) {
+                column_defaults.push(spec);
+            }
+        }
+
+        let mut grid = Self::table_grid(&table.rows);
+
+        for (r, c) in &grid.overlaps {
+            let line = table.rows[*r].line;
+            self.diagnostics.push(Diagnostic {
+                line,
+                message: format!(
+                    "table cell {} overlaps a cell spanning from an earlier row",
+                    c + 1,
+                ),
+            });
+        }
+
+        for (r, c) in &grid.overruns {
+            let line = table.rows[*r].line;
+            self.diagnostics.push(Diagnostic {
+                line,
+                message: format!(
+                    "table cell {} spans beyond the last row of the table",
+                    c + 1,
+                ),
+            });
+        }
+
+        for (row, covered) in table.rows.iter().zip(&grid.covered) {
+            if *covered < grid.width {
+                self.diagnostics.push(Diagnostic {
+                    line: row.line,
+                    message: format!(
+                        "table row covers {covered} of {} columns",
+                        grid.width,
+                    ),
+                });
+            }
+        }
+
+        if self.options.pad_tables {
+            for (row, covered) in table.rows.iter_mut().zip(&grid.covered) {
+                for _ in *covered..grid.width {
+                    row.cells.push(TableCell {
+                        colspan: 1,
+                        rowspan: 1,
+                        ..Default::default()
+                    });
+                }
+            }
+
+            grid = Self::table_grid(&table.rows);
+        }
+
+        for (row, columns) in table.rows.iter_mut().zip(grid.columns) {
+            for (cell, column) in row.cells.iter_mut().zip(columns) {
+                if let Some(spec) = column_defaults.get(column) {
+                    cell.scope = cell.scope.or(spec.scope);
+                    cell.align = cell.align.or(spec.align);
+                    cell.valign = cell.valign.or(spec.valign);
+                }
+            }
+        }
+
+        // leading rows made up of only column headers form the head, unless a
+        // row span would carry one of its cells into the body
+        let mut head = table.rows.iter()
+            .take_while(|row| {
+                !row.footer && !row.cells.is_empty() &&
+                    row.cells.iter().all(|cell| cell.scope == Some(Some("col")))
+            })
+            .count();
+        while head > 0 && table.rows[0..head].iter().enumerate().any(|(i, row)| {
+            row.cells.iter().any(|cell| i + cell.rowspan > head)
+        }) {
+            head -= 1;
+        }
+
+        let (head_rows, rest) = table.rows.split_at(head);
+
+        if let Some(row) = table.rows.first() {
+            self.mark_line(row.line);
+        }
+        self.output.push_str("<table>");
+
+        if let Some(caption) = &table.caption {
+            self.output.push_str("<caption>");
+            self.output.push_str(caption);
+            self.output.push_str("</caption>");
+        }
+
+        if !column_specs.is_empty() {
+            self.output.push_str("<colgroup>");
+            for (spec, width) in column_specs.iter().zip(&column_widths) {
+                self.output.push_str("<col");
+                if spec.colspan > 1 {
+                    self.output.push_str(" span=\"");
+                    self.output.push_str(&spec.colspan.to_string());
+                    self.output.push('"');
+                }
+                if is_length(width) {
+                    self.output.push_str(" style=\"width:");
+                    self.output.push_str(width);
+                    self.output.push('"');
+                }
+                self.output.push_str(" />");
+            }
+            self.output.push_str("</colgroup>");
+        }
+
+        // other formats are rendered from the html, which only holds the
+        // alignments given in the source so that they can tell them apart
+        let alignment = match self.options.format {
+            Format::Html => self.options.table_alignment.clone(),
+            _ => TableAlignment::Style,
+        };
+
+        if !head_rows.is_empty() {
+            self.output.push_str("<thead>");
+            for row in head_rows {
+                self.mark_line(row.line);
+                Self::write_table_row(&mut self.output, row, &alignment);
+            }
+            self.output.push_str("</thead>");
+        }
+
+        if rest.iter().any(|row| !row.footer) {
+            self.output.push_str("<tbody>");
+            for row in rest.iter().filter(|row| !row.footer) {
+                self.mark_line(row.line);
+                Self::write_table_row(&mut self.output, row, &alignment);
+            }
+            self.output.push_str("</tbody>");
+        }
+
+        if rest.iter().any(|row| row.footer) {
+            self.output.push_str("<tfoot>");
+            for row in rest.iter().filter(|row| row.footer) {
+                self.mark_line(row.line);
+                Self::write_table_row(&mut self.output, row, &alignment);
+            }
+            self.output.push_str("</tfoot>");
+        }
+
+        self.output.push_str("</table>\n");
+        self.mark_line(self.line_number);
+    }
+
     fn parse_inline(&mut self) {
         while !self.is_empty() {
             if self.escape("\\\\") ||
@@ -693,9 +2147,12 @@ impl<'a> Parser<'a> {
                 // encode html
                 self.output.push_str("&lt;");
             }
+
             else if !self.in_code && (
                 // br
                 self.br() ||
+                // br within a table cell
+                self.table_cell_br() ||
                 // strong
                 self.strong() ||
                 // em
@@ -714,6 +2171,8 @@ impl<'a> Parser<'a> {
                 self.unchecked() ||
                 // checkbox checked
                 self.checked() ||
+                // footnote
+                self.footnote_reference() ||
                 // link
                 self.link() ||
                 // image
@@ -726,10 +2185,13 @@ impl<'a> Parser<'a> {
             // text
             else {
                 // trim end padding in tables
-                if self.in_table && self.starts_with_trimmed_char('|') {
+                if self.in_table && !self.in_code && self.starts_with_trimmed_char('|') {
                     self.trim_start();
                 } else {
-                    let character = match self.consume(1) {
+                    let n = self.char_len();
+                    let ampersand = if self.in_code { self.code_ampersand() } else { "&" };
+                    let character = match self.consume(n) {
+                        "&" => ampersand,
                         "<" => "&lt;",
                         ">" => "&gt;",
                         c => c,
@@ -744,7 +2206,18 @@ impl<'a> Parser<'a> {
         macro_rules! revert_paragraph {
             () => {
                 if self.in_paragraph {
-                    self.output.push_str("</p>\n");
+                    match self.footnote_definition.take() {
+                        Some((label, start, line)) => {
+                            let content = self.output.split_off(start);
+                            self.unmark_from(start);
+                            self.footnote_definitions.push(FootnoteDefinition {
+                                label,
+                                line,
+                                content,
+                            });
+                        },
+                        None => self.output.push_str("</p>\n"),
+                    }
                     self.in_paragraph = false;
                 }
             }
@@ -782,14 +2255,7 @@ impl<'a> Parser<'a> {
         macro_rules! revert_table {
             () => {
                 if self.in_table {
-                    match &self.table_td_close {
-                        Some(close) => {
-                            self.output.push_str(close);
-                            self.table_td_close = None;
-                        },
-                        None => {},
-                    }
-                    self.output.push_str("</tr></tbody></table>\n");
+                    self.write_table();
                     self.in_table = false;
                 }
             }
@@ -922,35 +2388,35 @@ impl<'a> Parser<'a> {
             }
         }
 
+        self.front_matter();
+        if self.options.heading_ids {
+            self.reserve_heading_ids();
+        }
+
         loop {
             if !self.advance_line() {
                 break;
             }
 
+            if !self.in_an_element() {
+                self.report_ambiguous_heading();
+            }
+
             // end element
             if self.is_empty() {
+                // a footnote definition leaves nothing to separate
+                let in_definition = self.footnote_definition.is_some();
                 revert_all!();
-                self.output.push('\n');
-                continue;
-            }
-            // h6
-            else if !self.in_an_element() && self.line.starts_with("######") {
-                revert_all!();
-                self.advance(6);
-                self.output.push_str("<h6>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h6>\n");
+                if !in_definition {
+                    self.output.push('\n');
+                }
                 continue;
             }
-            // h5
-            else if !self.in_an_element() && self.line.starts_with("#####") {
+            // heading
+            else if !self.in_an_element() && self.heading_level().is_some() {
                 revert_all!();
-                self.advance(5);
-                self.output.push_str("<h5>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h5>\n");
+                let level = self.heading_level().unwrap();
+                self.heading(level);
                 continue;
             }
             // hr
@@ -960,18 +2426,8 @@ impl<'a> Parser<'a> {
                 self.output.push_str("<hr />\n");
                 continue;
             }
-            // h4
-            else if !self.in_an_element() && self.line.starts_with("####") {
-                revert_all!();
-                self.advance(4);
-                self.output.push_str("<h4>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h4>\n");
-                continue;
-            }
             // ul
-            else if !self.in_pre_code && !self.in_paragraph && !self.in_blockquote && !self.in_table && !self.in_code_fence && self.starts_with_trimmed_char('-') {
+            else if !self.in_pre_code && !self.in_paragraph && !self.in_blockquote && !self.in_table && !self.in_code_fence && self.starts_with_trimmed_marker("-") {
                 revert_all_but_list!();
                 self.handle_list_level_discrepancy("-", "<ul>", "</ul>");
                 self.trim_start();
@@ -979,9 +2435,11 @@ impl<'a> Parser<'a> {
                 continue;
             }
             // ol
-            else if !self.in_pre_code && !self.in_paragraph && !self.in_blockquote && !self.in_table && !self.in_code_fence && self.starts_with_trimmed("0.") {
+            else if !self.in_pre_code && !self.in_paragraph && !self.in_blockquote && !self.in_table && !self.in_code_fence && self.ordered_list_marker().is_some() {
                 revert_all_but_list!();
-                self.handle_list_level_discrepancy("0.", "<ol>", "</ol>");
+                let (marker, open) = self.ordered_list_marker().unwrap();
+                self.check_list_start(marker);
+                self.handle_list_level_discrepancy(marker, &open, "</ol>");
                 self.trim_start();
                 self.parse_inline();
                 continue;
@@ -999,15 +2457,17 @@ impl<'a> Parser<'a> {
 
                 // do NOT parse code blocks
                 while !self.is_empty() {
-                    // encode <, >, and space
-                    let character = match &self.line[0..1] {
+                    let n = self.char_len();
+                    // encode &, <, >, and space
+                    let character = match &self.line[0..n] {
+                        "&" => self.code_ampersand(),
                         "<" => "&lt;",
                         ">" => "&gt;",
                         " " => "&nbsp;",
                         c => c,
                     };
                     self.output.push_str(character);
-                    self.advance(1);
+                    self.advance(n);
                 }
 
                 continue;
@@ -1026,14 +2486,15 @@ impl<'a> Parser<'a> {
                     if !self.line.is_empty() {
                         let mut lang = String::new();
                         while !self.line.is_empty() {
-                            lang.push_str(match &self.line[0..1] {
+                            let n = self.char_len();
+                            lang.push_str(match &self.line[0..n] {
                                 "<" => "&lt;",
                                 ">" => "&gt;",
                                 "\"" => "",
                                 "\'" => "",
                                 c => c,
                             });
-                            self.advance(1);
+                            self.advance(n);
                         }
 
                         self.output.push_str("<pre><code lang=\"");
@@ -1061,48 +2522,20 @@ impl<'a> Parser<'a> {
                     if self.line.starts_with("\\`") {
                         self.advance(1);
                     }
-                    // encode <, >, and space
-                    self.output.push_str(match &self.line[0..1] {
+                    let n = self.char_len();
+                    // encode &, <, >, and space
+                    self.output.push_str(match &self.line[0..n] {
+                        "&" => self.code_ampersand(),
                         "<" => "&lt;",
                         ">" => "&gt;",
                         " " => "&nbsp;",
                         c => c,
                     });
-                    self.advance(1);
+                    self.advance(n);
                 }
 
                 continue;
             }
-            // h3
-            else if !self.in_an_element() && self.line.starts_with("###") {
-                revert_all!();
-                self.advance(3);
-                self.output.push_str("<h3>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h3>\n");
-                continue;
-            }
-            // h2
-            else if !self.in_an_element() && self.line.starts_with("##") {
-                revert_all!();
-                self.advance(2);
-                self.output.push_str("<h2>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h2>\n");
-                continue;
-            }
-            // h1
-            else if !self.in_an_element() && self.line.starts_with('#') {
-                revert_all!();
-                self.advance(1);
-                self.output.push_str("<h1>");
-                self.trim_start();
-                self.parse_inline();
-                self.output.push_str("</h1>\n");
-                continue;
-            }
             // blockquote
             else if !self.in_pre_code && !self.in_paragraph && !self.in_table && !self.in_code_fence && !self.in_list() && self.line.starts_with('>') {
                 self.advance(1);
@@ -1129,20 +2562,73 @@ impl<'a> Parser<'a> {
                 self.in_table && self.starts_with_trimmed_char('|')
             ) {
                 revert_all_but_table!();
-                if !self.in_table {
-                    self.output.push_str("<table><tbody><tr>");
+                self.in_table = true;
+                self.close_table_cell();
+
+                // continuation of the cells of the current row
+                if self.line.trim_start_matches(' ').starts_with("|&") &&
+                    !self.table.rows.is_empty()
+                {
+                    self.table.continuing = Some(0);
+                    self.parse_inline();
+                    self.table.continuing = None;
+                    continue;
+                }
+
+                // caption
+                if self.line.trim_start_matches(' ').starts_with("|#") {
+                    self.trim_start();
+                    self.advance(2);
+                    self.trim_start();
+
+                    // pipes are not cells within a caption
+                    let start = self.output.len();
+                    self.in_table = false;
+                    self.parse_inline();
                     self.in_table = true;
-                } else {
-                    match &self.table_td_close {
-                        Some(close) => {
-                            self.output.push_str(close);
-                            self.table_td_close = None;
+                    let caption = self.output.split_off(start);
+                    self.unmark_from(start);
+
+                    match &mut self.table.caption {
+                        Some(c) => {
+                            c.push('\n');
+                            c.push_str(&caption);
                         },
-                        None => {},
+                        None => self.table.caption = Some(caption),
                     }
-                    self.output.push_str("</tr><tr>");
+
+                    continue;
                 }
 
+                self.table.rows.push(TableRow {
+                    line: self.line_number,
+                    ..Default::default()
+                });
+                self.parse_inline();
+                continue;
+            }
+            // table of contents
+            else if !self.in_an_element() && self.line.trim_end_matches(' ') == "[TOC]" {
+                revert_all!();
+                self.toc_positions.push((self.output.len(), self.line_number));
+                continue;
+            }
+            // footnote definition
+            else if !self.in_an_element() && self.footnote_label().is_some() &&
+                self.line[self.footnote_label().unwrap().len() + 3..].starts_with(':')
+            {
+                revert_all!();
+                let label = self.footnote_label().unwrap();
+                self.advance(label.len() + 4);
+                self.trim_start();
+
+                // collected like a paragraph, but kept out of the output
+                self.footnote_definition = Some((
+                    label.to_owned(),
+                    self.output.len(),
+                    self.line_number,
+                ));
+                self.in_paragraph = true;
                 self.parse_inline();
                 continue;
             }
@@ -1164,7 +2650,7 @@ impl<'a> Parser<'a> {
 
             // escape all block level
             if self.line.starts_with("\\#") || self.line.starts_with("\\-") ||
-                self.line.starts_with("\\>") || self.line.starts_with("\\0") ||
+                self.line.starts_with("\\>") || self.escapes_number() ||
                 self.line.starts_with("\\|") || self.line.starts_with("\\ ") ||
                 self.line.starts_with("\\`")
             {
@@ -1179,17 +2665,20 @@ impl<'a> Parser<'a> {
 
         revert_all!();
         revert_code_fence!();
+        self.write_footnotes();
     }
 
-    fn from_str(input: &'a str) -> Self {
+    fn from_str(input: &'a str, options: Options) -> Self {
         Self {
             source: input.lines(),
             line: "",
+            line_number: 0,
+            options,
             in_paragraph: false,
             in_pre_code: false,
             in_blockquote: false,
             in_table: false,
-            table_td_close: None,
+            table: Table::default(),
             in_code_fence: false,
             code_fence_first: false,
             in_strong: false,
@@ -1201,23 +2690,92 @@ impl<'a> Parser<'a> {
             in_sup: false,
             list_nest: Vec::new(),
             output: String::new(),
+            diagnostics: Vec::new(),
+            footnote_definition: None,
+            footnotes: Vec::new(),
+            footnote_definitions: Vec::new(),
+            heading_ids: Vec::new(),
+            headings: Vec::new(),
+            metadata: Metadata::default(),
+            toc_positions: Vec::new(),
+            #[cfg(feature = "json")]
+            positions: Vec::new(),
+        }
+    }
+
+    fn into_result(self) -> ParseResult {
+        let mut result = ParseResult {
+            output: self.output,
+            diagnostics: self.diagnostics,
+            headings: self.headings,
+            metadata: self.metadata,
+        };
+
+        #[cfg(feature = "json")]
+        let mut positions = self.positions;
+
+        // tables of contents can only be written once every heading is known
+        if !self.toc_positions.is_empty() {
+            let mut toc = result.toc(self.options.toc_min_level, self.options.toc_max_level);
+            toc.push('\n');
+            for (position, _line) in self.toc_positions.iter().rev() {
+                result.output.insert_str(*position, &toc);
+
+                // the lines after the table of contents are moved past it
+                #[cfg(feature = "json")]
+                for (offset, line) in &mut positions {
+                    if *offset > *position || (*offset == *position && *line > *_line) {
+                        *offset += toc.len();
+                    }
+                }
+            }
         }
+
+        match self.options.format {
+            Format::Text => result.output = text::render(&result.output),
+            Format::CommonMark => result.output = commonmark::render(&result.output),
+            Format::Ansi => result.output = ansi::render(&result.output, self.options.width),
+            Format::Man => result.output = man::render(&result.output, &result.metadata),
+            Format::Latex => result.output = latex::render(&result.output),
+            Format::Gemini => result.output = gemini::render(&result.output),
+            #[cfg(feature = "json")]
+            Format::Json => result.output = json::render(&result, &positions),
+            Format::Html => if let Some(template) = &self.options.template {
+                result.output = result.fill_template(
+                    template,
+                    self.options.toc_min_level,
+                    self.options.toc_max_level,
+                );
+            } else if let Some(document) = &self.options.document {
+                result.output = result.document(document);
+            },
+        }
+
+        result
     }
 
     pub fn parse_str(input: &'a str) -> String {
-        let mut p = Self::from_str(input);
+        Self::parse_str_with(input, Options::default()).output
+    }
+
+    pub fn parse_str_with(input: &'a str, options: Options) -> ParseResult {
+        let mut p = Self::from_str(input, options);
         p.parse();
-        p.output
+        p.into_result()
     }
 
     pub fn parse_file<P: AsRef<Path>>(path: P) -> IOResult<String> {
+        Ok(Parser::parse_file_with(path, Options::default())?.output)
+    }
+
+    pub fn parse_file_with<P: AsRef<Path>>(path: P, options: Options) -> IOResult<ParseResult> {
         let mut file = OpenOptions::new()
             .read(true)
             .open(path)?;
         let mut s = String::new();
         file.read_to_string(&mut s)?;
-        let mut p = Parser::from_str(&s);
+        let mut p = Parser::from_str(&s, options);
         p.parse();
-        Ok(p.output)
+        Ok(p.into_result())
     }
 }
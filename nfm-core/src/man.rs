@@ -0,0 +1,362 @@
+// nfm-core::man: Renders No-Flavor Markdown as a roff man page.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown as a man page, using the macros of man(7). The
+//! level one heading becomes the title of the page, level two headings become
+//! sections and level three headings subsections.
+
+use crate::{
+    Metadata,
+    text::{ self, ordered_markers, },
+    tree::{ self, Element, Node, },
+};
+
+/// Renders the html written by the parser as roff. The section of the page is
+/// taken from a level one heading such as `nfm(1)`, or the `section` metadata
+/// entry, and the date from the `date` entry.
+pub fn render(html: &str, metadata: &Metadata) -> String {
+    let mut lines = Vec::new();
+    blocks(&tree::parse(html), metadata, &mut lines);
+
+    let mut roff = lines.join("\n");
+    if !roff.is_empty() {
+        roff.push('\n');
+    }
+    roff
+}
+
+/// The fonts of roff which may be combined by nesting inline elements.
+#[derive(Debug, Clone, Copy, Default)]
+struct Font {
+    bold: bool,
+    italic: bool,
+}
+
+impl Font {
+    fn escape(self) -> &'static str {
+        match (self.bold, self.italic) {
+            (false, false) => "\\fR",
+            (true, false) => "\\fB",
+            (false, true) => "\\fI",
+            (true, true) => "\\f(BI",
+        }
+    }
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into paragraphs of their own.
+fn blocks(nodes: &[Node], metadata: &Metadata, lines: &mut Vec<String>) {
+    let mut start = 0;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let roff = inline(&nodes[start..$end]);
+            if !roff.is_empty() {
+                lines.push(".PP".to_owned());
+                lines.push(roff);
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+                block(element, metadata, lines);
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+}
+
+fn block(element: &Element, metadata: &Metadata, lines: &mut Vec<String>) {
+    match element.name.as_str() {
+        "h1" => lines.push(title(&element.text(), metadata)),
+        "h2"|"h3" => {
+            let roff = heading(element);
+            if !roff.is_empty() {
+                lines.push(if element.name == "h2" { ".SH" } else { ".SS" }.to_owned());
+                lines.push(roff);
+            }
+        },
+        "h4"|"h5"|"h6" => {
+            let roff = heading(element);
+            if !roff.is_empty() {
+                lines.push(".PP".to_owned());
+                lines.push(format!("\\fB{roff}\\fR"));
+            }
+        },
+        "p" => {
+            let roff = inline(&element.children);
+            if !roff.is_empty() {
+                lines.push(".PP".to_owned());
+                lines.push(roff);
+            }
+        },
+        "hr" => lines.push(".sp".to_owned()),
+        "pre" => {
+            lines.push(".PP".to_owned());
+            lines.push(".RS 4".to_owned());
+            lines.push(".nf".to_owned());
+            lines.extend(element.text().trim_end_matches('\n').split('\n').map(literal));
+            lines.push(".fi".to_owned());
+            lines.push(".RE".to_owned());
+        },
+        // tables are laid out as they would be in plain text
+        "table" => {
+            lines.push(".PP".to_owned());
+            lines.push(".nf".to_owned());
+            lines.extend(text::render(&element.to_html()).lines().map(literal));
+            lines.push(".fi".to_owned());
+        },
+        "blockquote" => {
+            lines.push(".RS 4".to_owned());
+            blocks(&element.children, metadata, lines);
+            lines.push(".RE".to_owned());
+        },
+        "ul"|"ol" => list(element, metadata, lines),
+        "section" if element.attribute("class") == Some("footnotes") => {
+            footnotes(element, metadata, lines);
+        },
+        "colgroup" => {},
+        _ => blocks(&element.children, metadata, lines),
+    }
+}
+
+/// Writes the content of a heading on a single line, as breaks can't be
+/// written within the argument of a request.
+fn heading(element: &Element) -> String {
+    inline(&element.children)
+        .lines()
+        .filter(|l| *l != ".br")
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes the title of the page from the level one heading.
+fn title(heading: &str, metadata: &Metadata) -> String {
+    let heading = heading.trim();
+    let (name, section) = match heading.strip_suffix(')').and_then(|h| h.rsplit_once('(')) {
+        Some((name, section)) if !name.is_empty() && !section.is_empty() => {
+            (name, section)
+        },
+        _ => (heading, metadata.get("section").unwrap_or("1")),
+    };
+
+    let mut title = format!(".TH {} {}", argument(&name.to_uppercase()), argument(section));
+    if let Some(date) = metadata.get("date") {
+        title.push(' ');
+        title.push_str(&argument(date));
+    }
+    title
+}
+
+/// Quotes an argument of a request.
+fn argument(text: &str) -> String {
+    format!("\"{}\"", escape(text).replace('"', "\\(dq"))
+}
+
+/// Escapes the characters of text which roff would otherwise read as escapes,
+/// writing hyphens as minus signs so that options can be copied.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\e").replace('-', "\\-")
+}
+
+/// Escapes a line written as is, guarding a leading `.` or `'` which would
+/// otherwise begin a request.
+fn literal(line: &str) -> String {
+    let line = escape(line);
+    if line.starts_with('.') || line.starts_with('\'') {
+        format!("\\&{line}")
+    } else {
+        line
+    }
+}
+
+fn inline(nodes: &[Node]) -> String {
+    let mut roff = String::new();
+    push_inline(&mut roff, nodes, Font::default());
+
+    let roff = roff.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>();
+    roff.join("\n")
+}
+
+/// Writes text, guarding the beginning of each line against being read as a
+/// request.
+fn push_text(roff: &mut String, text: &str) {
+    for c in text.chars() {
+        let line_start = roff.trim_end_matches([ ' ', '\t', ]).is_empty() ||
+            roff.trim_end_matches([ ' ', '\t', ]).ends_with('\n');
+
+        match c {
+            '.'|'\'' if line_start => roff.push_str("\\&"),
+            '\\' => {
+                roff.push_str("\\e");
+                continue;
+            },
+            '-' => {
+                roff.push_str("\\-");
+                continue;
+            },
+            _ => {},
+        }
+
+        roff.push(c);
+    }
+}
+
+fn push_inline(roff: &mut String, nodes: &[Node], font: Font) {
+    for node in nodes {
+        let element = match node {
+            Node::Text(t) => {
+                push_text(roff, t);
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        match element.name.as_str() {
+            "br" => roff.push_str("\n.br\n"),
+            "strong" => styled(roff, element, font, Font { bold: true, ..font }),
+            "em"|"ins" => styled(roff, element, font, Font { italic: true, ..font }),
+            "code" => {
+                let inner = Font { bold: true, ..font };
+                roff.push_str(inner.escape());
+                push_text(roff, &element.text());
+                roff.push_str(font.escape());
+            },
+            "img" => push_text(roff, element.attribute("alt").unwrap_or_default()),
+            "input" => if element.has_attribute("checked") {
+                roff.push_str("[x]");
+            } else {
+                roff.push_str("[ ]");
+            },
+            "sup" => match element.footnote_reference() {
+                Some(number) => roff.push_str(&format!("[{number}]")),
+                None => push_inline(roff, &element.children, font),
+            },
+            "a" => match element.attribute("href") {
+                // links back to a footnote reference are only useful in html
+                Some(href) if href.starts_with("#fnref-") => {},
+                Some(href) if href.starts_with('#') => {
+                    push_inline(roff, &element.children, font);
+                },
+                Some(href) if element.text().is_empty() || element.text() == href => {
+                    push_text(roff, href);
+                },
+                Some(href) => {
+                    push_inline(roff, &element.children, font);
+                    push_text(roff, &format!(" <{href}>"));
+                },
+                None => push_inline(roff, &element.children, font),
+            },
+            _ => push_inline(roff, &element.children, font),
+        }
+    }
+}
+
+/// Writes the content of an element in a font, returning to the font around
+/// it afterwards.
+fn styled(roff: &mut String, element: &Element, outer: Font, inner: Font) {
+    roff.push_str(inner.escape());
+    push_inline(roff, &element.children, inner);
+    roff.push_str(outer.escape());
+}
+
+fn list(element: &Element, metadata: &Metadata, lines: &mut Vec<String>) {
+    let items = element.elements()
+        .filter(|e| e.name == "li")
+        .collect::<Vec<_>>();
+
+    let markers = if element.name == "ol" {
+        ordered_markers(element, items.len())
+    } else {
+        vec!["\\(bu".to_owned(); items.len()]
+    };
+
+    let indent = markers.iter()
+        .map(|m| if m == "\\(bu" { 1 } else { m.chars().count() })
+        .max()
+        .unwrap_or(1) + 1;
+
+    for (item, marker) in items.iter().zip(markers) {
+        lines.push(format!(".IP {marker} {indent}"));
+        item_content(&item.children, indent, metadata, lines);
+    }
+}
+
+/// Writes the content of a list item or footnote, indenting the blocks within
+/// it to the text of the item.
+fn item_content(nodes: &[Node], indent: usize, metadata: &Metadata, lines: &mut Vec<String>) {
+    let mut start = 0;
+    // the first paragraph follows the marker of the item
+    let begin = lines.len();
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let roff = inline(&nodes[start..$end]);
+            if !roff.is_empty() {
+                if lines.len() != begin {
+                    lines.push(format!(".IP \"\" {indent}"));
+                }
+                lines.push(roff);
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+
+                if element.name == "p" {
+                    let roff = inline(&element.children);
+                    if lines.len() != begin {
+                        lines.push(format!(".IP \"\" {indent}"));
+                    }
+                    lines.push(roff);
+                } else {
+                    lines.push(format!(".RS {indent}"));
+                    block(element, metadata, lines);
+                    lines.push(".RE".to_owned());
+                }
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+}
+
+fn footnotes(element: &Element, metadata: &Metadata, lines: &mut Vec<String>) {
+    lines.push(".sp".to_owned());
+    for list in element.elements() {
+        for item in list.elements() {
+            let number = item.attribute("id")
+                .and_then(|id| id.strip_prefix("fn-"))
+                .unwrap_or_default();
+            let marker = format!("[{number}]");
+            let indent = marker.chars().count() + 1;
+            lines.push(format!(".IP {marker} {indent}"));
+            item_content(&item.children, indent, metadata, lines);
+        }
+    }
+}
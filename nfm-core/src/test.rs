@@ -16,7 +16,7 @@
 
 //! The primary tests for No-Flavor Markdown.
 
-use crate::Parser;
+use crate::{ convert, formatter, Document, Format, Options, Parser, TableAlignment, };
 
 #[test]
 fn anchor_1() {
@@ -102,6 +102,12 @@ fn link_2() {
     assert_eq!(include_str!("../tests/link/2/against.html"), html);
 }
 
+#[test]
+fn link_3() {
+    let html = Parser::parse_str(include_str!("../tests/link/3/test.md"));
+    assert_eq!(include_str!("../tests/link/3/against.html"), html);
+}
+
 #[test]
 fn list_1() {
     let html = Parser::parse_str(include_str!("../tests/list/1/test.md"));
@@ -138,6 +144,47 @@ fn list_6() {
     assert_eq!(include_str!("../tests/list/6/against.html"), html);
 }
 
+#[test]
+fn list_7() {
+    let html = Parser::parse_str(include_str!("../tests/list/7/test.md"));
+    assert_eq!(include_str!("../tests/list/7/against.html"), html);
+}
+
+#[test]
+fn list_8() {
+    let html = Parser::parse_str(include_str!("../tests/list/8/test.md"));
+    assert_eq!(include_str!("../tests/list/8/against.html"), html);
+}
+
+#[test]
+fn list_9() {
+    let html = Parser::parse_str(include_str!("../tests/list/9/test.md"));
+    assert_eq!(include_str!("../tests/list/9/against.html"), html);
+}
+
+#[test]
+fn list_10() {
+    let html = Parser::parse_str(include_str!("../tests/list/10/test.md"));
+    assert_eq!(include_str!("../tests/list/10/against.html"), html);
+}
+
+#[test]
+fn list_11() {
+    let html = Parser::parse_str(include_str!("../tests/list/11/test.md"));
+    assert_eq!(include_str!("../tests/list/11/against.html"), html);
+}
+
+#[test]
+fn list_12() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/list/12/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/list/12/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 9, ], lines);
+}
+
 #[test]
 fn del_1() {
     let html = Parser::parse_str(include_str!("../tests/del/1/test.md"));
@@ -156,6 +203,39 @@ fn em_2() {
     assert_eq!(include_str!("../tests/em/2/against.html"), html);
 }
 
+#[test]
+fn footnote_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/footnote/1/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/footnote/1/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 1, 12, ], lines);
+}
+
+#[test]
+fn footnote_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/footnote/2/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/footnote/2/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 1, ], lines);
+}
+
+#[test]
+fn footnote_3() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/footnote/3/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/footnote/3/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 3, 1, ], lines);
+}
+
 #[test]
 fn h1_1() {
     let html = Parser::parse_str(include_str!("../tests/h1/1/test.md"));
@@ -168,6 +248,326 @@ fn h1_2() {
     assert_eq!(include_str!("../tests/h1/2/against.html"), html);
 }
 
+#[test]
+fn h1_3() {
+    let html = Parser::parse_str(include_str!("../tests/h1/3/test.md"));
+    assert_eq!(include_str!("../tests/h1/3/against.html"), html);
+}
+
+#[test]
+fn heading_ids_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/heading-ids/1/test.md"),
+        Options { heading_ids: true, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/heading-ids/1/against.html"), result.output);
+}
+
+#[test]
+fn heading_ids_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/heading-ids/2/test.md"),
+        Options { heading_ids: true, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/heading-ids/2/against.html"), result.output);
+}
+
+#[test]
+fn toc_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/toc/1/test.md"),
+        Options { heading_ids: true, toc_min_level: 2, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/toc/1/against.html"), result.output);
+}
+
+#[test]
+fn metadata_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/metadata/1/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/metadata/1/against.html"), result.output);
+    assert_eq!(Some("Front Matter"), result.metadata.get("title"));
+    assert_eq!(Some("2024-05-01"), result.metadata.get("date"));
+    assert_eq!(vec![ "markdown", "html", ], result.metadata.list("tags"));
+    assert_eq!(4, result.metadata.len());
+}
+
+#[test]
+fn metadata_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/metadata/2/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/metadata/2/against.html"), result.output);
+    assert!(result.metadata.is_empty());
+}
+
+#[test]
+fn metadata_3() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/metadata/3/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/metadata/3/against.html"), result.output);
+    assert!(result.metadata.is_empty());
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn document_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/document/1/test.md"),
+        Options {
+            document: Some(Document {
+                lang: Some("en-GB".to_owned()),
+                stylesheets: vec![ "style.css?v=\"2\"&w='3'".to_owned(), ],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    assert_eq!(include_str!("../tests/document/1/against.html"), result.output);
+}
+
+#[test]
+fn template_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/template/1/test.md"),
+        Options {
+            heading_ids: true,
+            template: Some(include_str!("../tests/template/1/template.html").to_owned()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(include_str!("../tests/template/1/against.html"), result.output);
+}
+
+#[test]
+fn template_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/template/2/test.md"),
+        Options {
+            template: Some(include_str!("../tests/template/2/template.html").to_owned()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(include_str!("../tests/template/2/against.html"), result.output);
+}
+
+#[test]
+fn text_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/text/1/test.md"),
+        Options { format: Format::Text, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/text/1/against.txt"), result.output);
+}
+
+#[test]
+fn text_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/text/2/test.md"),
+        Options { format: Format::Text, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/text/2/against.txt"), result.output);
+}
+
+#[test]
+fn text_3() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/text/3/test.md"),
+        Options { format: Format::Text, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/text/3/against.txt"), result.output);
+}
+
+#[test]
+fn commonmark_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/commonmark/1/test.md"),
+        Options { format: Format::CommonMark, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/commonmark/1/against.md"), result.output);
+}
+
+#[test]
+fn commonmark_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/commonmark/2/test.md"),
+        Options { format: Format::CommonMark, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/commonmark/2/against.md"), result.output);
+}
+
+#[test]
+fn commonmark_3() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/commonmark/3/test.md"),
+        Options { format: Format::CommonMark, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/commonmark/3/against.md"), result.output);
+}
+
+#[test]
+fn ansi_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/ansi/1/test.md"),
+        Options { format: Format::Ansi, width: 40, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/ansi/1/against.txt"), result.output);
+}
+
+#[test]
+fn ansi_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/ansi/2/test.md"),
+        Options { format: Format::Ansi, width: 40, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/ansi/2/against.txt"), result.output);
+}
+
+#[test]
+fn man_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/man/1/test.md"),
+        Options { format: Format::Man, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/man/1/against.1"), result.output);
+}
+
+#[test]
+fn man_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/man/2/test.md"),
+        Options { format: Format::Man, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/man/2/against.1"), result.output);
+}
+
+#[test]
+fn latex_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/latex/1/test.md"),
+        Options { format: Format::Latex, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/latex/1/against.tex"), result.output);
+}
+
+#[test]
+fn latex_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/latex/2/test.md"),
+        Options { format: Format::Latex, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/latex/2/against.tex"), result.output);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/json/1/test.md"),
+        Options { format: Format::Json, heading_ids: true, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/json/1/against.json"), result.output);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/json/2/test.md"),
+        Options { format: Format::Json, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/json/2/against.json"), result.output);
+}
+
+#[test]
+fn gemini_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/gemini/1/test.md"),
+        Options { format: Format::Gemini, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/gemini/1/against.gmi"), result.output);
+}
+
+#[test]
+fn gemini_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/gemini/2/test.md"),
+        Options { format: Format::Gemini, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/gemini/2/against.gmi"), result.output);
+}
+
+#[test]
+fn convert_1() {
+    let conversion = convert::from_commonmark(include_str!("../tests/convert/1/test.md"));
+    assert_eq!(include_str!("../tests/convert/1/against.md"), conversion.output);
+    assert_eq!(
+        vec![ 10, 12, ],
+        conversion.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>(),
+    );
+}
+
+#[test]
+fn convert_2() {
+    let conversion = convert::from_commonmark(include_str!("../tests/convert/2/test.md"));
+    assert_eq!(include_str!("../tests/convert/2/against.md"), conversion.output);
+    assert!(conversion.diagnostics.is_empty());
+}
+
+#[test]
+fn convert_3() {
+    // text which No-Flavor Markdown would read as markup stays text
+    let conversion = convert::from_commonmark(include_str!("../tests/convert/3/test.md"));
+    assert!(conversion.diagnostics.is_empty());
+    let result = Parser::parse_str_with(&conversion.output, Options::default());
+    assert_eq!(include_str!("../tests/convert/3/against.html"), result.output);
+    assert!(result.diagnostics.is_empty());
+}
+
+/// The html of a document split at whitespace, which is the same however the
+/// text of the document is wrapped.
+fn words(html: &str) -> Vec<&str> {
+    html.split_whitespace().collect()
+}
+
+#[test]
+fn fmt_1() {
+    let formatted = formatter::format(include_str!("../tests/fmt/1/test.md"));
+    assert_eq!(include_str!("../tests/fmt/1/against.md"), formatted);
+    assert_eq!(formatted, formatter::format(&formatted));
+    assert_eq!(
+        words(&Parser::parse_str(include_str!("../tests/fmt/1/test.md"))),
+        words(&Parser::parse_str(&formatted)),
+    );
+}
+
+#[test]
+fn fmt_2() {
+    let options = || formatter::FormatOptions { width: Some(40), };
+    let formatted = formatter::format_with(include_str!("../tests/fmt/2/test.md"), options());
+    assert_eq!(include_str!("../tests/fmt/2/against.md"), formatted);
+    assert_eq!(formatted, formatter::format_with(&formatted, options()));
+    assert_eq!(
+        words(&Parser::parse_str(include_str!("../tests/fmt/2/test.md"))),
+        words(&Parser::parse_str(&formatted)),
+    );
+}
+
+#[test]
+fn fmt_3() {
+    let formatted = formatter::format(include_str!("../tests/fmt/3/test.md"));
+    assert_eq!(include_str!("../tests/fmt/3/against.md"), formatted);
+    assert_eq!(
+        words(&Parser::parse_str(include_str!("../tests/fmt/3/test.md"))),
+        words(&Parser::parse_str(&formatted)),
+    );
+}
+
 #[test]
 fn h2_1() {
     let html = Parser::parse_str(include_str!("../tests/h2/1/test.md"));
@@ -329,3 +729,145 @@ fn table_5() {
     let html = Parser::parse_str(include_str!("../tests/table/5/test.md"));
     assert_eq!(include_str!("../tests/table/5/against.html"), html);
 }
+
+#[test]
+fn table_6() {
+    let html = Parser::parse_str(include_str!("../tests/table/6/test.md"));
+    assert_eq!(include_str!("../tests/table/6/against.html"), html);
+}
+
+#[test]
+fn table_7() {
+    let html = Parser::parse_str(include_str!("../tests/table/7/test.md"));
+    assert_eq!(include_str!("../tests/table/7/against.html"), html);
+}
+
+#[test]
+fn table_8() {
+    let html = Parser::parse_str(include_str!("../tests/table/8/test.md"));
+    assert_eq!(include_str!("../tests/table/8/against.html"), html);
+}
+
+#[test]
+fn table_9() {
+    let html = Parser::parse_str(include_str!("../tests/table/9/test.md"));
+    assert_eq!(include_str!("../tests/table/9/against.html"), html);
+}
+
+#[test]
+fn table_10() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/10/test.md"),
+        Options { pad_tables: true, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/table/10/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 3, 4, ], lines);
+}
+
+#[test]
+fn table_11() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/11/test.md"),
+        Options { table_alignment: TableAlignment::Style, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/table/11/against.html"), result.output);
+}
+
+#[test]
+fn table_12() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/12/test.md"),
+        Options {
+            table_alignment: TableAlignment::Classes("nfm-".to_owned()),
+            ..Default::default()
+        },
+    );
+    assert_eq!(include_str!("../tests/table/12/against.html"), result.output);
+}
+
+#[test]
+fn table_13() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/13/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/table/13/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 9, ], lines);
+}
+
+#[test]
+fn table_14() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/14/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/table/14/against.html"), result.output);
+    assert!(result.diagnostics.is_empty());
+}
+
+#[test]
+fn table_15() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/15/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/table/15/against.html"), result.output);
+}
+
+#[test]
+fn table_16() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/16/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/table/16/against.html"), result.output);
+}
+
+#[test]
+fn table_17() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/table/17/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/table/17/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 1, 1, ], lines);
+}
+
+#[test]
+fn table_grid_1() {
+    for md in [
+        include_str!("../tests/table/1/test.md"),
+        include_str!("../tests/table/2/test.md"),
+        include_str!("../tests/table/3/test.md"),
+        include_str!("../tests/table/4/test.md"),
+        include_str!("../tests/table/9/test.md"),
+    ] {
+        let result = Parser::parse_str_with(md, Options::default());
+        assert!(result.diagnostics.is_empty(), "{:?}", result.diagnostics);
+    }
+}
+
+#[test]
+fn strict_headings_1() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/strict-headings/1/test.md"),
+        Options { strict_headings: true, ..Default::default() },
+    );
+    assert_eq!(include_str!("../tests/strict-headings/1/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 1, 3, 7, ], lines);
+}
+
+#[test]
+fn strict_headings_2() {
+    let result = Parser::parse_str_with(
+        include_str!("../tests/strict-headings/2/test.md"),
+        Options::default(),
+    );
+    assert_eq!(include_str!("../tests/strict-headings/2/against.html"), result.output);
+    let lines = result.diagnostics.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(vec![ 1, 3, 7, ], lines);
+}
@@ -0,0 +1,387 @@
+// nfm-core::text: Renders No-Flavor Markdown as plain text.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Renders No-Flavor Markdown as plain text.
+
+use crate::tree::{ self, Element, Node, };
+
+/// Renders the html written by the parser as plain text.
+pub fn render(html: &str) -> String {
+    let mut text = blocks(&tree::parse(html)).join("\n\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text
+}
+
+/// Renders a run of nodes as blocks, gathering inline nodes between block
+/// elements into blocks of their own.
+fn blocks(nodes: &[Node]) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+
+    macro_rules! push_inline {
+        ($end:expr) => {
+            let text = inline(&nodes[start..$end]);
+            let text = text.trim();
+            if !text.is_empty() {
+                blocks.push(text.to_owned());
+            }
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        if let Node::Element(element) = node {
+            if element.is_block() {
+                push_inline!(i);
+                start = i + 1;
+                if let Some(block) = block(element) {
+                    blocks.push(block);
+                }
+            }
+        }
+    }
+
+    push_inline!(nodes.len());
+    blocks
+}
+
+fn block(element: &Element) -> Option<String> {
+    let text = match element.name.as_str() {
+        "hr" => "* * *".to_owned(),
+        "pre" => element.text().trim_end_matches('\n').to_owned(),
+        "blockquote" => blocks(&element.children).join("\n\n")
+            .lines()
+            .map(|l| if l.is_empty() { ">".to_owned() } else { format!("> {l}") })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "ul"|"ol" => list(element),
+        "table" => table(element),
+        "section" if element.attribute("class") == Some("footnotes") => {
+            footnotes(element)
+        },
+        "colgroup" => return None,
+        "h1"|"h2"|"h3"|"h4"|"h5"|"h6"|"p"|"caption" => {
+            inline(&element.children).trim().to_owned()
+        },
+        _ => blocks(&element.children).join("\n\n"),
+    };
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+fn inline(nodes: &[Node]) -> String {
+    let mut text = String::new();
+
+    for (i, node) in nodes.iter().enumerate() {
+        let element = match node {
+            Node::Text(t) => {
+                text.push_str(t);
+                continue;
+            },
+            Node::Element(element) => element,
+        };
+
+        match element.name.as_str() {
+            // the break is usually followed by the line break of the source
+            "br" => match nodes.get(i + 1) {
+                Some(Node::Text(t)) if t.starts_with('\n') => {},
+                _ => text.push('\n'),
+            },
+            "img" => text.push_str(element.attribute("alt").unwrap_or_default()),
+            "input" => if element.has_attribute("checked") {
+                text.push_str("[x]");
+            } else {
+                text.push_str("[ ]");
+            },
+            "sup" => match element.footnote_reference() {
+                Some(number) => text.push_str(&format!("[{number}]")),
+                None => text.push_str(&inline(&element.children)),
+            },
+            "a" => text.push_str(&link(element)),
+            _ => if element.is_block() {
+                text.push_str(&blocks(&element.children).join("\n"));
+            } else {
+                text.push_str(&inline(&element.children));
+            },
+        }
+    }
+
+    text
+}
+
+fn link(element: &Element) -> String {
+    let text = inline(&element.children);
+    match element.attribute("href") {
+        // links back to a footnote reference are only useful in html
+        Some(href) if href.starts_with("#fnref-") => String::new(),
+        Some(href) if href.starts_with('#') => text,
+        Some(href) if text.is_empty() || text == href => href.to_owned(),
+        Some(href) => format!("{text} ({href})"),
+        None => text,
+    }
+}
+
+fn letters(mut n: usize, base: u8) -> String {
+    let mut s = Vec::new();
+    while n > 0 {
+        n -= 1;
+        s.push(base + (n % 26) as u8);
+        n /= 26;
+    }
+    s.reverse();
+    String::from_utf8(s).unwrap()
+}
+
+fn roman(mut n: usize) -> String {
+    const NUMERALS: &[(usize, &str)] = &[
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+
+    let mut s = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= *value {
+            s.push_str(numeral);
+            n -= value;
+        }
+    }
+    s
+}
+
+/// The largest number written as letters or roman numerals, past which
+/// markers are written as numbers as they are in html.
+const MAX_NUMERAL: usize = 3999;
+
+/// The marker of each item of an ordered list, as it would be numbered in
+/// html.
+pub(crate) fn ordered_markers(element: &Element, count: usize) -> Vec<String> {
+    let reversed = element.has_attribute("reversed");
+    let start = element.attribute("start")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(if reversed { count } else { 1 });
+
+    (0..count).map(|i| {
+        let n = if reversed { start as i128 - i as i128 } else { start as i128 + i as i128 };
+        let marker = match (element.attribute("type"), usize::try_from(n)) {
+            (Some("a"), Ok(n @ 1..=MAX_NUMERAL)) => letters(n, b'a'),
+            (Some("A"), Ok(n @ 1..=MAX_NUMERAL)) => letters(n, b'A'),
+            (Some("i"), Ok(n @ 1..=MAX_NUMERAL)) => roman(n),
+            (Some("I"), Ok(n @ 1..=MAX_NUMERAL)) => roman(n).to_uppercase(),
+            _ => n.to_string(),
+        };
+        format!("{marker}.")
+    }).collect()
+}
+
+fn list(element: &Element) -> String {
+    let items = element.elements()
+        .filter(|e| e.name == "li")
+        .collect::<Vec<_>>();
+
+    let markers = if element.name == "ol" {
+        ordered_markers(element, items.len())
+    } else {
+        vec!["-".to_owned(); items.len()]
+    };
+
+    let mut lines = Vec::new();
+    for (item, marker) in items.iter().zip(markers) {
+        let indent = " ".repeat(marker.chars().count() + 1);
+        let content = blocks(&item.children).join("\n");
+        let mut content = content.lines();
+
+        lines.push(format!("{marker} {}", content.next().unwrap_or_default()).trim_end().to_owned());
+        for line in content {
+            if line.is_empty() {
+                lines.push(String::new());
+            } else {
+                lines.push(format!("{indent}{line}"));
+            }
+        }
+    }
+
+    lines.join("\n")
+}
+
+fn footnotes(element: &Element) -> String {
+    let mut lines = Vec::new();
+    for list in element.elements() {
+        for item in list.elements() {
+            let number = item.attribute("id")
+                .and_then(|id| id.strip_prefix("fn-"))
+                .unwrap_or_default();
+            let content = blocks(&item.children).join("\n");
+            lines.push(format!("[{number}] {}", content.trim()));
+        }
+    }
+
+    lines.join("\n")
+}
+
+struct Cell {
+    lines: Vec<String>,
+    column: usize,
+    colspan: usize,
+    align: String,
+}
+
+fn width(s: &str) -> usize {
+    s.chars().count()
+}
+
+fn table(element: &Element) -> String {
+    const GAP: &str = "  ";
+
+    let mut caption = None;
+    // each row, and whether a rule is drawn beneath it
+    let mut rows = Vec::new();
+
+    for child in element.elements() {
+        match child.name.as_str() {
+            "caption" => caption = block(child),
+            "thead"|"tbody"|"tfoot" => {
+                // the head is ruled off from the body, and the foot from both
+                if child.name == "tfoot" {
+                    if let Some((_, rule)) = rows.last_mut() {
+                        *rule = true;
+                    }
+                }
+
+                let count = rows.len();
+                rows.extend(child.elements().filter(|e| e.name == "tr").map(|r| (r, false)));
+                if child.name == "thead" && rows.len() > count {
+                    rows.last_mut().unwrap().1 = true;
+                }
+            },
+            "tr" => rows.push((child, false)),
+            _ => {},
+        }
+    }
+
+    // place every cell in its column, skipping those covered by a row span
+    let mut covered: Vec<usize> = Vec::new();
+    let mut grid = Vec::new();
+    for (row, _) in &rows {
+        let mut cells = Vec::new();
+        let mut column = 0;
+
+        for cell in row.elements().filter(|e| e.name == "td" || e.name == "th") {
+            while covered.get(column).is_some_and(|c| *c > 0) {
+                column += 1;
+            }
+
+            let colspan = cell.span("colspan");
+            let rowspan = cell.span("rowspan");
+            if covered.len() < column + colspan {
+                covered.resize(column + colspan, 0);
+            }
+            for c in &mut covered[column..column + colspan] {
+                *c = rowspan;
+            }
+
+            cells.push(Cell {
+                lines: blocks(&cell.children).join("\n").lines().map(str::to_owned).collect(),
+                column,
+                colspan,
+                align: cell.alignment().unwrap_or("center").to_owned(),
+            });
+            column += colspan;
+        }
+
+        for c in &mut covered {
+            *c = c.saturating_sub(1);
+        }
+
+        grid.push(cells);
+    }
+
+    let columns = grid.iter()
+        .flat_map(|cells| cells.iter().map(|c| c.column + c.colspan))
+        .max()
+        .unwrap_or(0);
+    let mut widths = vec![0; columns];
+
+    for cell in grid.iter().flatten().filter(|c| c.colspan == 1) {
+        let w = cell.lines.iter().map(|l| width(l)).max().unwrap_or(0);
+        widths[cell.column] = widths[cell.column].max(w);
+    }
+
+    // spanning cells widen the last of their columns when they don't fit
+    for cell in grid.iter().flatten().filter(|c| c.colspan > 1) {
+        let w = cell.lines.iter().map(|l| width(l)).max().unwrap_or(0);
+        let span = &widths[cell.column..cell.column + cell.colspan];
+        let available = span.iter().sum::<usize>() + GAP.len() * (cell.colspan - 1);
+        if w > available {
+            widths[cell.column + cell.colspan - 1] += w - available;
+        }
+    }
+
+    let mut lines = Vec::new();
+    if let Some(caption) = caption {
+        lines.push(caption);
+    }
+
+    for (cells, (_, rule)) in grid.iter().zip(&rows) {
+        let height = cells.iter().map(|c| c.lines.len()).max().unwrap_or(0).max(1);
+
+        for l in 0..height {
+            let mut line = String::new();
+            let mut column = 0;
+
+            for cell in cells {
+                while column < cell.column {
+                    line.push_str(&" ".repeat(widths[column]));
+                    line.push_str(GAP);
+                    column += 1;
+                }
+
+                let span = &widths[cell.column..cell.column + cell.colspan];
+                let w = span.iter().sum::<usize>() + GAP.len() * (cell.colspan - 1);
+                let text = cell.lines.get(l).map(String::as_str).unwrap_or_default();
+                let padding = w.saturating_sub(width(text));
+                let (left, right) = match cell.align.as_str() {
+                    "right" => (padding, 0),
+                    "center" => (padding / 2, padding - padding / 2),
+                    _ => (0, padding),
+                };
+
+                line.push_str(&" ".repeat(left));
+                line.push_str(text);
+                line.push_str(&" ".repeat(right));
+                line.push_str(GAP);
+                column += cell.colspan;
+            }
+
+            lines.push(line.trim_end().to_owned());
+        }
+
+        if *rule {
+            lines.push(
+                widths.iter()
+                    .map(|w| "-".repeat(*w))
+                    .collect::<Vec<_>>()
+                    .join(GAP)
+            );
+        }
+    }
+
+    lines.join("\n")
+}
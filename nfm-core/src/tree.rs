@@ -0,0 +1,404 @@
+// nfm-core::tree: The elements of a parsed document.
+// Copyright (C) 2024  Frankie Baffa
+// 
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+// 
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+// 
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The elements of a parsed document.
+//!
+//! The parser writes html as it goes, so the other formats are rendered from
+//! a tree read back out of that html. Only the html written by the parser
+//! needs to be understood, which is a small and well-behaved subset.
+
+/// Elements which never have children or a closing tag.
+const VOID_ELEMENTS: &[&str] = &[ "br", "col", "hr", "img", "input", "link", "meta", ];
+
+/// Elements which contain blocks rather than inline content.
+const BLOCK_ELEMENTS: &[&str] = &[
+    "blockquote", "caption", "colgroup", "h1", "h2", "h3", "h4", "h5", "h6",
+    "hr", "li", "nav", "ol", "p", "pre", "section", "table", "tbody", "td",
+    "tfoot", "th", "thead", "tr", "ul",
+];
+
+/// A piece of a parsed document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Node {
+    /// The text within the node, with every tag removed.
+    pub fn text(&self) -> String {
+        match self {
+            Node::Element(element) => element.text(),
+            Node::Text(text) => text.clone(),
+        }
+    }
+
+    pub fn is_block(&self) -> bool {
+        match self {
+            Node::Element(element) => element.is_block(),
+            Node::Text(_) => false,
+        }
+    }
+
+    /// Writes the node back out as html.
+    pub fn to_html(&self) -> String {
+        match self {
+            Node::Element(element) => element.to_html(),
+            Node::Text(text) => encode(text),
+        }
+    }
+}
+
+/// An element of a parsed document, such as a paragraph or a link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+    /// Where the opening tag of the element begins in the html it was read
+    /// from.
+    pub offset: usize,
+}
+
+impl Element {
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attribute(name).is_some()
+    }
+
+    /// A numeric attribute, such as a span, defaulting to one.
+    pub fn span(&self, name: &str) -> usize {
+        self.attribute(name)
+            .and_then(|v| v.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(1)
+    }
+
+    /// The horizontal alignment of a table cell, however it was written.
+    pub fn alignment(&self) -> Option<&str> {
+        if let Some(align) = self.attribute("align") {
+            return Some(align);
+        }
+
+        if let Some(style) = self.attribute("style") {
+            return style.split(';')
+                .filter_map(|s| s.split_once(':'))
+                .find(|(k, _)| k.trim() == "text-align")
+                .map(|(_, v)| v.trim());
+        }
+
+        self.attribute("class")?
+            .split(' ')
+            .find_map(|c| c.rsplit_once("align-").filter(|(p, _)| !p.ends_with('v')))
+            .map(|(_, a)| a)
+    }
+
+    /// The vertical alignment of a table cell, however it was written.
+    pub fn vertical_alignment(&self) -> Option<&str> {
+        if let Some(valign) = self.attribute("valign") {
+            return Some(valign);
+        }
+
+        if let Some(style) = self.attribute("style") {
+            return style.split(';')
+                .filter_map(|s| s.split_once(':'))
+                .find(|(k, _)| k.trim() == "vertical-align")
+                .map(|(_, v)| v.trim());
+        }
+
+        self.attribute("class")?
+            .split(' ')
+            .find_map(|c| c.rsplit_once("valign-"))
+            .map(|(_, a)| a)
+    }
+
+    /// The element children of the element.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|c| match c {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// The text within the element, with every tag removed.
+    pub fn text(&self) -> String {
+        self.children.iter().map(Node::text).collect()
+    }
+
+    /// The number of the footnote a superscript refers to.
+    pub fn footnote_reference(&self) -> Option<String> {
+        let mut elements = self.elements();
+        let link = elements.next()?;
+        if self.name != "sup" || elements.next().is_some() || link.name != "a" {
+            return None;
+        }
+
+        link.attribute("href")
+            .filter(|h| h.starts_with("#fn-"))
+            .map(|_| link.text())
+    }
+
+    pub fn is_block(&self) -> bool {
+        BLOCK_ELEMENTS.contains(&self.name.as_str())
+    }
+
+    /// Writes the element back out as html.
+    pub fn to_html(&self) -> String {
+        let mut html = format!("<{}", self.name);
+        for (name, value) in &self.attributes {
+            html.push_str(&format!(" {name}=\"{}\"", encode(value).replace('"', "&quot;")));
+        }
+
+        if VOID_ELEMENTS.contains(&self.name.as_str()) {
+            html.push_str(" />");
+            return html;
+        }
+
+        html.push('>');
+        for child in &self.children {
+            html.push_str(&child.to_html());
+        }
+        html.push_str(&format!("</{}>", self.name));
+        html
+    }
+}
+
+fn encode(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Decodes the character references written by the parser and the common
+/// named references written in the source.
+pub(crate) fn decode(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_owned();
+    }
+
+    let mut decoded = String::new();
+    let mut rest = text;
+
+    while let Some(idx) = rest.find('&') {
+        decoded.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        let reference = rest.find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| {
+                let c = match &rest[1..end] {
+                    "nbsp" => Some(' '),
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "amp" => Some('&'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    "copy" => Some('©'),
+                    "reg" => Some('®'),
+                    "trade" => Some('™'),
+                    "deg" => Some('°'),
+                    "sect" => Some('§'),
+                    "para" => Some('¶'),
+                    "middot" => Some('·'),
+                    "times" => Some('×'),
+                    "divide" => Some('÷'),
+                    "plusmn" => Some('±'),
+                    "cent" => Some('¢'),
+                    "pound" => Some('£'),
+                    "yen" => Some('¥'),
+                    "euro" => Some('€'),
+                    "laquo" => Some('«'),
+                    "raquo" => Some('»'),
+                    "lsquo" => Some('‘'),
+                    "rsquo" => Some('’'),
+                    "ldquo" => Some('“'),
+                    "rdquo" => Some('”'),
+                    "ndash" => Some('–'),
+                    "mdash" => Some('—'),
+                    "hellip" => Some('…'),
+                    "bull" => Some('•'),
+                    n if n.starts_with("#x") || n.starts_with("#X") => {
+                        u32::from_str_radix(&n[2..], 16).ok().and_then(char::from_u32)
+                    },
+                    n if n.starts_with('#') => {
+                        n[1..].parse().ok().and_then(char::from_u32)
+                    },
+                    _ => None,
+                };
+
+                c.map(|c| (c, end))
+            });
+
+        match reference {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            },
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    decoded.push_str(rest);
+    decoded
+}
+
+/// An opening tag read from html.
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(String, String)>,
+    /// Whether the tag closes itself.
+    closed: bool,
+    len: usize,
+}
+
+/// Reads the tag at the start of html.
+fn read_tag(html: &str) -> Option<Tag<'_>> {
+    let bytes = html.as_bytes();
+    let mut i = 1;
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+        i += 1;
+    }
+
+    if i == 1 || !bytes[1].is_ascii_alphabetic() {
+        return None;
+    }
+
+    let name = &html[1..i];
+    let mut attributes = Vec::new();
+
+    loop {
+        while i < bytes.len() && bytes[i] == b' ' {
+            i += 1;
+        }
+
+        if i >= bytes.len() {
+            return None;
+        }
+
+        match bytes[i] {
+            b'>' => return Some(Tag { name, attributes, closed: false, len: i + 1, }),
+            b'/' if bytes.get(i + 1) == Some(&b'>') => {
+                return Some(Tag { name, attributes, closed: true, len: i + 2, });
+            },
+            _ => {},
+        }
+
+        let start = i;
+        while i < bytes.len() && !matches!(bytes[i], b'=' | b' ' | b'>' | b'/') {
+            i += 1;
+        }
+
+        if start == i {
+            return None;
+        }
+
+        let key = html[start..i].to_owned();
+        if bytes.get(i) == Some(&b'=') && bytes.get(i + 1) == Some(&b'"') {
+            let end = html[i + 2..].find('"')? + i + 2;
+            attributes.push((key, decode(&html[i + 2..end])));
+            i = end + 1;
+        } else {
+            attributes.push((key, String::new()));
+        }
+    }
+}
+
+/// Reads the html written by the parser back into a tree of nodes.
+pub fn parse(html: &str) -> Vec<Node> {
+    let mut stack: Vec<Element> = vec![Element {
+        name: String::new(),
+        attributes: Vec::new(),
+        children: Vec::new(),
+        offset: 0,
+    }];
+    let mut text = String::new();
+    let mut rest = html;
+
+    macro_rules! flush_text {
+        () => {
+            if !text.is_empty() {
+                let t = decode(&text);
+                stack.last_mut().unwrap().children.push(Node::Text(t));
+                text.clear();
+            }
+        }
+    }
+
+    while let Some(idx) = rest.find('<') {
+        text.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+
+        // a closing tag ends the nearest open element of the same name
+        if let Some(close) = rest.strip_prefix("</") {
+            if let Some(end) = close.find('>') {
+                let name = &close[..end];
+                if stack.iter().skip(1).any(|e| e.name == name) {
+                    flush_text!();
+                    while let Some(element) = stack.pop() {
+                        let found = element.name == name;
+                        stack.last_mut().unwrap().children.push(Node::Element(element));
+                        if found {
+                            break;
+                        }
+                    }
+
+                    rest = &close[end + 1..];
+                    continue;
+                }
+            }
+        }
+
+        match read_tag(rest) {
+            Some(Tag { name, attributes, closed, len, }) => {
+                flush_text!();
+                let element = Element {
+                    name: name.to_owned(),
+                    attributes,
+                    children: Vec::new(),
+                    offset: html.len() - rest.len(),
+                };
+
+                if closed || VOID_ELEMENTS.contains(&name) {
+                    stack.last_mut().unwrap().children.push(Node::Element(element));
+                } else {
+                    stack.push(element);
+                }
+
+                rest = &rest[len..];
+            },
+            None => {
+                text.push('<');
+                rest = &rest[1..];
+            },
+        }
+    }
+
+    text.push_str(rest);
+    flush_text!();
+
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        stack.last_mut().unwrap().children.push(Node::Element(element));
+    }
+
+    stack.pop().unwrap().children
+}
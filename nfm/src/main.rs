@@ -18,11 +18,15 @@
 
 use {
     args::{ Arguments, OptionType, },
-    nfm_core::Parser,
+    nfm_core::{
+        convert, formatter::{ self, FormatOptions, }, Document, Format, Options, Parser,
+        TableAlignment,
+    },
     std::{
         fs::OpenOptions,
-        io::{ Error, ErrorKind, Read, Result, stdin, Write, },
-        process::exit,
+        env,
+        io::{ Error, ErrorKind, IsTerminal, Read, Result, stdin, stdout, Write, },
+        process::{ self, exit, Stdio, },
         time::Instant,
     },
 };
@@ -31,13 +35,150 @@ const HELP: &str = include_str!("../resources/help.txt");
 const LICENSE_NOTICE: &str = include_str!("../../NOTICE-GPL");
 const LICENSE_FULL: &str = include_str!("../../LICENSE-GPL");
 
+/// Takes the value following an argument, returning an error when there is
+/// none.
+macro_rules! value_of {
+    ($args:expr, $name:literal) => {
+        match $args.next_arg() {
+            Some(a) => match a.option_type() {
+                OptionType::Argument(_) => return Err(Error::other(
+                    concat!($name, " requires a value."),
+                )),
+                OptionType::Value(_) => a.qualifier().to_owned(),
+            },
+            None => return Err(Error::other(
+                concat!($name, " requires a value."),
+            )),
+        }
+    }
+}
+
+fn read_file(path: String) -> Result<String> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .open(path)?;
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+    Ok(s)
+}
+
+/// Reads the input of a command from stdin or the file at path.
+fn read_input(read_stdin: bool, path: Option<String>) -> Result<String> {
+    if read_stdin {
+        let mut input = String::new();
+        stdin().lock().read_to_string(&mut input)?;
+        Ok(input)
+    } else if let Some(path) = path {
+        read_file(path)
+    } else {
+        eprintln!("Argument path must be provided when not reading from stdin.");
+        exit(2);
+    }
+}
+
+fn write_output(output: &str, output_path: Option<String>) -> Result<()> {
+    match output_path {
+        Some(path) => {
+            let mut file = OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?;
+            file.write_all(output.as_bytes())?;
+        },
+        None => {
+            print!("{output}");
+        },
+    }
+
+    Ok(())
+}
+
+/// The width of the terminal, read from `COLUMNS` or asked of `stty`.
+fn terminal_width() -> usize {
+    if let Some(columns) = env::var("COLUMNS").ok().and_then(|c| c.parse().ok()) {
+        return columns;
+    }
+
+    OpenOptions::new()
+        .read(true)
+        .open("/dev/tty")
+        .ok()
+        .and_then(|tty| process::Command::new("stty").arg("size").stdin(tty).output().ok())
+        .and_then(|o| String::from_utf8(o.stdout).ok()?.split_whitespace().nth(1)?.parse().ok())
+        .unwrap_or(80)
+}
+
+/// Shows output through the pager named by `PAGER`, or `less`, when writing
+/// to a terminal.
+fn page(output: &str) -> Result<()> {
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_owned());
+    let mut pager = pager.split_whitespace();
+
+    let program = match pager.next() {
+        Some(program) if stdout().is_terminal() => program,
+        _ => {
+            print!("{output}");
+            return Ok(());
+        },
+    };
+
+    let mut command = process::Command::new(program);
+    command.args(pager).stdin(Stdio::piped());
+
+    // as git does, let less show colours and quit when the output fits
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+
+    match command.spawn() {
+        Ok(mut child) => {
+            // the pager may be quit before reading everything
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(output.as_bytes());
+            }
+            child.wait()?;
+        },
+        Err(_) => print!("{output}"),
+    }
+
+    Ok(())
+}
+
+#[derive(PartialEq, Eq)]
+enum Command {
+    /// Converts another flavor of markdown into No-Flavor Markdown.
+    Convert,
+    /// Writes No-Flavor Markdown in a canonical form.
+    Format,
+    /// Shows No-Flavor Markdown in the terminal.
+    View,
+}
+
 #[derive(Default)]
 struct Args {
+    command: Option<Command>,
+    from: Option<String>,
+    check: bool,
+    width: Option<usize>,
     timing: bool,
     dry_run: bool,
     output_path: Option<String>,
     read_stdin: bool,
     path: Option<String>,
+    strict_headings: bool,
+    pad_tables: bool,
+    table_alignment: TableAlignment,
+    heading_ids: bool,
+    toc_min_level: Option<usize>,
+    toc_max_level: Option<usize>,
+    metadata: bool,
+    document: bool,
+    lang: Option<String>,
+    stylesheets: Vec<String>,
+    css_path: Option<String>,
+    template_path: Option<String>,
+    format: Format,
 }
 
 fn main() -> Result<()> {
@@ -51,22 +192,56 @@ fn main() -> Result<()> {
                 },
                 "t"|"timing" => b.timing = true,
                 "n"|"dry-run" => b.dry_run = true,
-                "o"|"output-path" => match a.next_arg() {
-                    Some(a) => match a.option_type() {
-                        OptionType::Argument(_) => return Err(
-                            Error::new(
-                                ErrorKind::Other,
-                                "-o|--output-path requires a value".to_owned(),
-                            )
-                        ),
-                        OptionType::Value(_) => b.output_path = Some(a.qualifier().to_owned()),
-                    },
-                    None => return Err(Error::new(
-                        ErrorKind::Other,
-                        "-o|--output-path requires a value.".to_owned(),
+                "o"|"output-path" => b.output_path = Some(
+                    value_of!(a, "-o|--output-path")
+                ),
+                "i"|"read-stdin" => b.read_stdin = true,
+                "s"|"strict-headings" => b.strict_headings = true,
+                "p"|"pad-tables" => b.pad_tables = true,
+                "I"|"heading-ids" => b.heading_ids = true,
+                "toc-min" => b.toc_min_level = Some(
+                    value_of!(a, "--toc-min").parse().map_err(|_| Error::other(
+                        "--toc-min requires a number.",
+                    ))?
+                ),
+                "toc-max" => b.toc_max_level = Some(
+                    value_of!(a, "--toc-max").parse().map_err(|_| Error::other(
+                        "--toc-max requires a number.",
+                    ))?
+                ),
+                "m"|"metadata" => b.metadata = true,
+                "from" => b.from = Some(value_of!(a, "--from")),
+                "check" => b.check = true,
+                "w"|"width" => b.width = Some(
+                    value_of!(a, "-w|--width").parse().map_err(|_| Error::other(
+                        "-w|--width requires a number.",
+                    ))?
+                ),
+                "f"|"format" => b.format = match value_of!(a, "-f|--format").as_str() {
+                    "html" => Format::Html,
+                    "text" => Format::Text,
+                    "commonmark" => Format::CommonMark,
+                    "ansi" => Format::Ansi,
+                    "man" => Format::Man,
+                    "latex" => Format::Latex,
+                    "gemini" => Format::Gemini,
+                    #[cfg(feature = "json")]
+                    "json" => Format::Json,
+                    f => return Err(Error::other(
+                        format!("Unknown format {f}."),
                     )),
                 },
-                "i"|"read-stdin" => b.read_stdin = true,
+                "d"|"document" => b.document = true,
+                "lang" => b.lang = Some(value_of!(a, "--lang")),
+                "stylesheet" => b.stylesheets.push(value_of!(a, "--stylesheet")),
+                "css" => b.css_path = Some(value_of!(a, "--css")),
+                "T"|"template" => b.template_path = Some(
+                    value_of!(a, "-T|--template")
+                ),
+                "S"|"table-style" => b.table_alignment = TableAlignment::Style,
+                "C"|"table-classes" => b.table_alignment = TableAlignment::Classes(
+                    value_of!(a, "-C|--table-classes")
+                ),
                 "l"|"license-notice" => {
                     println!("{LICENSE_NOTICE}");
                     std::process::exit(0);
@@ -81,7 +256,20 @@ fn main() -> Result<()> {
                 },
                 q => return Err(Error::new(ErrorKind::Other, q.to_string())),
             },
-            OptionType::Value(_) => if c.is_last() {
+            // a subcommand is the first value, whatever follows it
+            OptionType::Value(_) => if b.command.is_none() && b.path.is_none() &&
+                c.qualifier() == "convert"
+            {
+                b.command = Some(Command::Convert);
+            } else if b.command.is_none() && b.path.is_none() &&
+                c.qualifier() == "fmt"
+            {
+                b.command = Some(Command::Format);
+            } else if b.command.is_none() && b.path.is_none() &&
+                c.qualifier() == "view"
+            {
+                b.command = Some(Command::View);
+            } else if c.is_last() {
                 b.path = Some(c.qualifier().to_owned());
             } else {
                 return Err(
@@ -96,7 +284,108 @@ fn main() -> Result<()> {
         Ok(())
     })?;
 
-    let Args { timing, dry_run, output_path, path, read_stdin } = args;
+    let Args {
+        command, from, check, width, timing, dry_run, output_path, path, read_stdin,
+        strict_headings, pad_tables, table_alignment, heading_ids, toc_min_level, toc_max_level,
+        metadata, document, lang, stylesheets, css_path, template_path,
+        format,
+    } = args;
+
+    if command == Some(Command::Convert) {
+        let input = read_input(read_stdin, path)?;
+
+        let conversion = match from.as_deref().unwrap_or("commonmark") {
+            "commonmark" => convert::from_commonmark(&input),
+            f => return Err(Error::other(
+                format!("Unknown format {f}."),
+            )),
+        };
+
+        for diagnostic in &conversion.diagnostics {
+            eprintln!("warning: {diagnostic}");
+        }
+
+        if !dry_run {
+            write_output(&conversion.output, output_path)?;
+        }
+
+        return Ok(());
+    }
+
+    if command == Some(Command::Format) {
+        let name = path.clone().unwrap_or_else(|| "stdin".to_owned());
+        let input = read_input(read_stdin, path)?;
+        let formatted = formatter::format_with(&input, FormatOptions { width, });
+
+        // checking only reports whether the input is already formatted
+        if check {
+            if formatted != input {
+                eprintln!("{name} is not formatted.");
+                exit(1);
+            }
+
+            return Ok(());
+        }
+
+        if !dry_run {
+            write_output(&formatted, output_path)?;
+        }
+
+        return Ok(());
+    }
+
+    // any of the document arguments imply a document
+    let document = document || lang.is_some() || !stylesheets.is_empty() || css_path.is_some();
+
+    // a template takes the place of a document, so the two can't be combined
+    if document && template_path.is_some() {
+        return Err(Error::other(
+            "-T|--template can't be combined with -d|--document, --lang, --stylesheet or --css.",
+        ));
+    }
+
+    let template = match template_path {
+        Some(path) => Some(read_file(path)?),
+        None => None,
+    };
+
+    let document = if document {
+        let css = match css_path {
+            Some(path) => Some(read_file(path)?),
+            None => None,
+        };
+
+        Some(Document {
+            lang,
+            title: None,
+            stylesheets,
+            css,
+        })
+    } else {
+        None
+    };
+
+    // viewing is output for the terminal, shown through a pager
+    let viewing = command == Some(Command::View);
+    let format = if viewing { Format::Ansi } else { format };
+    let width = match width {
+        Some(width) => width,
+        None if format == Format::Ansi => terminal_width(),
+        None => 80,
+    };
+
+    let options = Options {
+        strict_headings,
+        pad_tables,
+        table_alignment,
+        heading_ids,
+        toc_min_level: toc_min_level.unwrap_or(1),
+        toc_max_level: toc_max_level.unwrap_or(6),
+        document,
+        template,
+        format,
+        width,
+    };
 
     let (output, dur) = if read_stdin {
             let stdin = stdin();
@@ -110,7 +399,7 @@ fn main() -> Result<()> {
             }
 
             let start = if timing { Some(Instant::now()) } else { None };
-            let output = Parser::parse_str(&input);
+            let output = Parser::parse_str_with(&input, options);
             let dur = if timing {
                 Some((Instant::now() - start.unwrap()).as_nanos() as f64 / 1000000000_f64)
             } else {
@@ -120,7 +409,7 @@ fn main() -> Result<()> {
             (output, dur)
     } else if let Some(path) = path {
         let start = if timing { Some(Instant::now()) } else { None };
-        let output = Parser::parse_file(path)?;
+        let output = Parser::parse_file_with(path, options)?;
         let dur = if timing {
             Some((Instant::now() - start.unwrap()).as_nanos() as f64 / 1000000000_f64)
         } else {
@@ -133,20 +422,20 @@ fn main() -> Result<()> {
         exit(2);
     };
 
-    if !dry_run {
-        match output_path {
-            Some(path) => {
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .truncate(true)
-                    .open(path)?;
-                file.write_all(output.as_bytes())?;
-            },
-            None => {
-                print!("{output}");
-            },
-        }
+    for diagnostic in &output.diagnostics {
+        eprintln!("warning: {diagnostic}");
+    }
+
+    let output = if metadata {
+        output.metadata.to_string()
+    } else {
+        output.output
+    };
+
+    if viewing && output_path.is_none() && !dry_run {
+        page(&output)?;
+    } else if !dry_run {
+        write_output(&output, output_path)?;
     }
 
     if timing {